@@ -1,7 +1,9 @@
 use clap::{
     app_from_crate, crate_authors, crate_description, crate_name, crate_version, Arg, SubCommand,
 };
-use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_config::RpcProgramAccountsConfig;
+use solana_client::rpc_filter::RpcFilterType;
 use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{read_keypair_file, Signer};
@@ -9,29 +11,239 @@ use solana_sdk::signature::{read_keypair_file, Signer};
 use solana_sdk::signer::signers::Signers;
 use solana_sdk::transaction::Transaction;
 use solana_sdk::system_program;
-use borsh::{BorshDeserialize, BorshSerialize,BorshSchema};
+use solana_sdk::system_instruction;
+use solana_sdk::signature::Signature;
 use solana_sdk::commitment_config::CommitmentConfig;
-use spl_token;
-use spl_associated_token_account;
-#[allow(unused_imports)]
+use solana_sdk::hash::Hash;
+use solana_transaction_status::UiTransactionEncoding;
 use solana_sdk::signer::keypair::Keypair;
+use token_sell::{
+    allowlist_leaf, allowlist_node, decode_event, is_closed_vault, is_current, treasury_wsol_address, unpack_account, AdminData, BlockedWallet, FillMode, LimitTarget, PaymentSource, Payout, PriceData, ReferrerData, Role, RoundPurchase,
+    SaleConfig, SaleRound, SaleState, SellEvent, SellInstruction, SplitShare, TreasuryData, Versioned, Voucher,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many accounts one `Migrate` transaction upgrades.
+const MIGRATE_BATCH: usize = 8;
+
+/// How many payouts one `Distribute` transaction carries. Each adds two accounts, so the
+/// transaction size runs out before the program's own limit does once co-signers are added.
+const DISTRIBUTE_BATCH: usize = 5;
+
+/// The "state" PDA, which instructions read for the PDA bumps `Generate` cached there.
+fn state_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&["state".as_bytes()], program_id).0
+}
+
+/// The ["blocked", wallet] PDA, which only exists while `wallet` is blocked.
+fn blocklist_address(program_id: &Pubkey, wallet: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&["blocked".as_bytes(), wallet.as_ref()], program_id).0
+}
+
+/// The canonical bump of `wallet`'s blocklist PDA, which `Buy` takes so it doesn't have to search for it.
+fn blocklist_bump(program_id: &Pubkey, wallet: &Pubkey) -> u8 {
+    Pubkey::find_program_address(&["blocked".as_bytes(), wallet.as_ref()], program_id).1
+}
+
+/// The "admin" PDA followed by every co-signer, as privileged instructions expect after their own accounts.
+fn admin_metas(program_id: &Pubkey, signers: &[Keypair]) -> Vec<AccountMeta> {
+    let (admin_address, _admin_bump) = Pubkey::find_program_address(&["admin".as_bytes()], program_id);
+    let mut metas = vec![AccountMeta::new_readonly(admin_address, false)];
+    for signer in signers.iter().skip(1){
+        metas.push(AccountMeta::new_readonly(signer.pubkey(), true));
+    }
+    metas
+}
+
+/// Reads `wallet,amount` rows, skipping blank lines, `#` comments and a header row.
+/// Rows are numbered from 1 as they appear in the file, which is what the progress file refers to.
+fn read_payouts(path: &str) -> Vec<(usize, Payout)> {
+    let content = fs::read_to_string(path).expect("Can't read csv file");
+    let mut payouts = Vec::new();
+    for (index, line) in content.lines().enumerate(){
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#'){
+            continue;
+        }
+        let mut fields = line.split(',').map(|field| field.trim());
+        let recipient = fields.next().unwrap_or_default().parse::<Pubkey>();
+        let amount = fields.next().unwrap_or_default().parse::<u64>();
+        match (recipient, amount){
+            (Ok(recipient), Ok(amount)) if amount>0=>payouts.push((index + 1, Payout{recipient, amount})),
+            _ if payouts.is_empty() && index==0=>continue,
+            _=>panic!("Bad csv row {}: {}", index + 1, line),
+        }
+    }
+    payouts
+}
+
+/// What a previous `distribute` run did with one row: the transaction it went out in and,
+/// until that is known to have landed, the blockhash it was signed with.
+struct RowProgress{
+    payout: Payout,
+    signature: Signature,
+    pending: Option<Hash>,
+}
+
+/// Reads what previous `distribute` runs sent, as `row,wallet,amount,signature` lines for paid rows
+/// and `row,wallet,amount,signature,blockhash` lines for rows sent but not confirmed. Lines are only
+/// ever appended, so a row's last line is the one that counts.
+fn read_progress(path: &str) -> HashMap<usize, RowProgress> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    content.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line|{
+            let fields: Vec<&str> = line.split(',').collect();
+            if fields.len()!=4 && fields.len()!=5{
+                panic!("Bad progress line: {}", line);
+            }
+            let row = fields[0].parse::<usize>().expect("Bad progress row");
+            let recipient = fields[1].parse::<Pubkey>().expect("Bad progress wallet");
+            let amount = fields[2].parse::<u64>().expect("Bad progress amount");
+            let signature = fields[3].parse::<Signature>().expect("Bad progress signature");
+            let pending = fields.get(4).map(|blockhash| blockhash.parse::<Hash>().expect("Bad progress blockhash"));
+            (row, RowProgress{payout: Payout{recipient, amount}, signature, pending})
+        })
+        .collect()
+}
+
+/// Appends a line per row of `batch`, see `read_progress`.
+fn record_progress(path: &str, batch: &[(usize, Payout)], signature: &Signature, pending: Option<&Hash>) {
+    let mut progress = fs::OpenOptions::new().create(true).append(true).open(path).expect("Can't open progress file");
+    for (row, payout) in batch.iter(){
+        match pending{
+            Some(blockhash)=>writeln!(progress, "{},{},{},{},{}", row, payout.recipient, payout.amount, signature, blockhash),
+            None=>writeln!(progress, "{},{},{},{}", row, payout.recipient, payout.amount, signature),
+        }.expect("Can't write progress file");
+    }
+    progress.sync_all().expect("Can't write progress file");
+}
+
+fn parse_role(role: &str) -> Role {
+    match role{
+        "price"=>Role::Price,
+        "withdraw"=>Role::Withdraw,
+        "pause"=>Role::Pause,
+        "config"=>Role::Config,
+        _=>panic!("role should be price, withdraw, pause or config"),
+    }
+}
+
+/// Vouchers travel as `buyer:max_amount:expiry:nonce:signer:signature`.
+fn encode_voucher(voucher: &Voucher, signer: &Pubkey, signature: &Signature) -> String {
+    format!("{}:{}:{}:{}:{}:{}", voucher.buyer, voucher.max_amount, voucher.expiry, voucher.nonce, signer, signature)
+}
+
+fn parse_voucher(voucher: &str) -> (Voucher, Pubkey, Signature) {
+    let fields: Vec<&str> = voucher.split(':').collect();
+    if fields.len()!=6{
+        panic!("Voucher should be buyer:max_amount:expiry:nonce:signer:signature");
+    }
+    let voucher = Voucher{
+        buyer: fields[0].parse::<Pubkey>().expect("Wrong voucher buyer"),
+        max_amount: fields[1].parse::<u64>().expect("Wrong voucher max_amount"),
+        expiry: fields[2].parse::<i64>().expect("Wrong voucher expiry"),
+        nonce: fields[3].parse::<u64>().expect("Wrong voucher nonce"),
+    };
+    let signer = fields[4].parse::<Pubkey>().expect("Wrong voucher signer");
+    let signature = fields[5].parse::<Signature>().expect("Wrong voucher signature");
+    (voucher, signer, signature)
+}
+
+/// An Ed25519 program instruction checking a signature made elsewhere. Laid out like
+/// `solana_sdk::ed25519_instruction`, with key, signature and message all in its own data.
+fn ed25519_instruction(signer: &Pubkey, signature: &Signature, message: &[u8]) -> Instruction {
+    let public_key_offset: u16 = 2 + 14;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
 
-#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
-enum SellInstruction{
-    Generate,
-    Buy{
-        #[allow(dead_code)]
-        amount:u64,
-    },
-    Withdraw{
-        #[allow(dead_code)]
-        amount:u64,
-    },
-    SetPrice{
-        #[allow(dead_code)]
-        price:u64,
-    },
-} 
+    let mut data = vec![1u8, 0];
+    for offset in [
+        signature_offset,
+        u16::MAX,
+        public_key_offset,
+        u16::MAX,
+        message_offset,
+        message.len() as u16,
+        u16::MAX,
+    ]{
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(signature.as_ref());
+    data.extend_from_slice(message);
+
+    Instruction{program_id: solana_sdk::ed25519_program::id(), accounts: vec![], data}
+}
+
+/// Reads one wallet per line, skipping blank lines and `#` comments.
+fn read_wallets(path: &str) -> Vec<Pubkey> {
+    fs::read_to_string(path).expect("Can't read wallet list")
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.parse::<Pubkey>().unwrap_or_else(|_| panic!("Bad wallet: {}", line)))
+        .collect()
+}
+
+/// Merkle root of `wallets` and, if `wallet` is one of them, its proof, paired the way `verify_allowlist` expects.
+fn allowlist_proof(wallets: &[Pubkey], wallet: &Pubkey) -> ([u8; 32], Option<Vec<[u8; 32]>>) {
+    let mut level: Vec<[u8; 32]> = wallets.iter().map(allowlist_leaf).collect();
+    level.sort();
+    level.dedup();
+
+    let leaf = allowlist_leaf(wallet);
+    let mut index = level.iter().position(|node| *node==leaf);
+    let mut proof = Vec::new();
+    while level.len()>1{
+        if let Some(position) = index{
+            if let Some(sibling) = level.get(position ^ 1){
+                proof.push(*sibling);
+            }
+            index = Some(position / 2);
+        }
+        // An odd node out moves up a level unchanged.
+        level = level.chunks(2)
+            .map(|pair| if pair.len()==2 { allowlist_node(&pair[0], &pair[1]) } else { pair[0] })
+            .collect();
+    }
+    (level.first().copied().unwrap_or_default(), index.map(|_| proof))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_root(hex: &str) -> [u8; 32] {
+    if hex.len()!=64{
+        panic!("Allowlist root should be 64 hex digits");
+    }
+    let mut root = [0u8; 32];
+    for (index, byte) in root.iter_mut().enumerate(){
+        *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).expect("Allowlist root should be hex");
+    }
+    root
+}
+
+/// Parses `start,end,price,allocation[,wallet_cap[,allowlist_root]]`.
+fn parse_round(round: &str) -> SaleRound {
+    let fields: Vec<&str> = round.split(',').map(|field| field.trim()).collect();
+    if fields.len()<4 || fields.len()>6{
+        panic!("Round should be start,end,price,allocation[,wallet_cap[,allowlist_root]]");
+    }
+    SaleRound{
+        start_ts: fields[0].parse::<i64>().expect("Round start should be a unix timestamp"),
+        end_ts: fields[1].parse::<i64>().expect("Round end should be a unix timestamp"),
+        price: fields[2].parse::<u64>().expect("Round price should be a number"),
+        allocation: fields[3].parse::<u64>().expect("Round allocation should be a number"),
+        wallet_cap: fields.get(4).map(|cap| cap.parse::<u64>().expect("Round wallet cap should be a number")).unwrap_or(0),
+        allowlist_root: fields.get(5).map(|root| parse_root(root)).unwrap_or_default(),
+        sold: 0,
+    }
+}
 
 fn main() {
     let matches = app_from_crate!()
@@ -53,8 +265,11 @@ fn main() {
             .arg(Arg::with_name("sign")
                 .short("s")
                 .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
                 .required(true)
                 .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
             )
             .arg(Arg::with_name("env")
                 .short("e")
@@ -88,6 +303,58 @@ fn main() {
                 .required(true)
                 .takes_value(true)
             )
+            .arg(Arg::with_name("referrer")
+                .short("r")
+                .long("referrer")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("recipient")
+                .short("t")
+                .long("recipient")
+                .help("Wallet that receives the tokens, the signer if omitted")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("voucher")
+                .short("v")
+                .long("voucher")
+                .help("Voucher from issue_voucher, needed once the sale has a voucher signer")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("gate_account")
+                .short("g")
+                .long("gate_account")
+                .help("Token account holding the gate mint, the buyer's ATA if omitted")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("allowlist")
+                .short("l")
+                .long("allowlist")
+                .help("Wallet list behind the active round's allowlist root")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("fill")
+                .short("f")
+                .long("fill")
+                .help("all to fail when the vault runs short, partial to buy what is left; all by default")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("wsol")
+                .short("w")
+                .long("wsol")
+                .help("Pay from the signer's wrapped SOL account instead of native SOL")
+                .required(false)
+            )
+            .arg(Arg::with_name("wrap")
+                .long("wrap")
+                .help("Wrap whatever SOL the wSOL account is short of in the same transaction; implies --wsol")
+                .required(false)
+            )
 
         )
         .subcommand(SubCommand::with_name("withdraw")
@@ -100,8 +367,11 @@ fn main() {
             .arg(Arg::with_name("sign")
                 .short("s")
                 .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
                 .required(true)
                 .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
             )
             .arg(Arg::with_name("env")
                 .short("e")
@@ -115,9 +385,16 @@ fn main() {
                 .required(false)
                 .takes_value(true)
             )
+            .arg(Arg::with_name("destination")
+                .short("d")
+                .long("destination")
+                .help("Wallet that receives the tokens, the signer if omitted")
+                .required(false)
+                .takes_value(true)
+            )
 
         )
-        .subcommand(SubCommand::with_name("set_price")
+        .subcommand(SubCommand::with_name("deposit")
             .arg(Arg::with_name("program_id")
                 .short("i")
                 .long("contract_id")
@@ -127,8 +404,11 @@ fn main() {
             .arg(Arg::with_name("sign")
                 .short("s")
                 .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
                 .required(true)
                 .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
             )
             .arg(Arg::with_name("env")
                 .short("e")
@@ -136,183 +416,2882 @@ fn main() {
                 .required(false)
                 .takes_value(true)
             )
-            .arg(Arg::with_name("price")
-                .short("p")
-                .long("price")
+            .arg(Arg::with_name("amount")
+                .short("a")
+                .long("amount")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("close_sale")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("destination")
+                .short("d")
+                .long("destination")
+                .help("Owner of the leftover tokens, the signer by default")
                 .required(false)
                 .takes_value(true)
             )
         )
-        .get_matches();
-
-    let treasury = "D4bMaJQG5EhGExPqT1tYR8Vyd39Gf2tzN4JoxAy42jgP".parse::<Pubkey>().unwrap();
-    let mint = "5CZJ7e4uUWCogW2z7rvcE2yur6yE7Z7kcGYrFqLLXSL9".parse::<Pubkey>().unwrap();
-
-    if let Some(matches) = matches.subcommand_matches("set_price") {
-        let url = match matches.value_of("env"){
-            Some("dev")=>"https://api.devnet.solana.com",
-            _=>"https://api.mainnet-beta.solana.com",
-        };
-        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
-        
-        let wallet_path = matches.value_of("sign").unwrap();
-        let wallet_keypair = read_keypair_file(wallet_path).expect("Can't open file-wallet");
-        let wallet_pubkey = wallet_keypair.pubkey();
-
-        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
-        
-        let price = matches.value_of("price").unwrap().parse::<u64>().expect("price should be a number");
-        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
-        let instarctions = vec![Instruction::new_with_borsh(
-            program_id,
-            &SellInstruction::SetPrice{price},
-            vec![
-                AccountMeta::new(wallet_pubkey, true),
-                AccountMeta::new(price_address, false),
-                AccountMeta::new(system_program::id(), false),
-                AccountMeta::new_readonly("SysvarRent111111111111111111111111111111111".parse::<Pubkey>().unwrap(), false),
-                
-            ],
-        )];
-        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
-        let (recent_blockhash, _) = client.get_recent_blockhash().expect("Can't get blockhash");
-        tx.sign(&vec![&wallet_keypair], recent_blockhash);
-        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
-        println!("Success. Check transaction: {:?}",hash);
-        
-    }
-
-    if let Some(matches) = matches.subcommand_matches("withdraw") {
-        let url = match matches.value_of("env"){
-            Some("dev")=>"https://api.devnet.solana.com",
-            _=>"https://api.mainnet-beta.solana.com",
-        };
-        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
-        
-        let wallet_path = matches.value_of("sign").unwrap();
-        let wallet_keypair = read_keypair_file(wallet_path).expect("Can't open file-wallet");
-        let wallet_pubkey = wallet_keypair.pubkey();
-
-        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
-        
-        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
-        let vault_mint_holder = spl_associated_token_account::get_associated_token_address(&vault_pda, &mint);
-        let wallet_mint_holder = spl_associated_token_account::get_associated_token_address(&wallet_pubkey, &mint);
-
-        let amount = if let Some(amount_str)=matches.value_of("amount"){
-            amount_str.parse::<u64>().unwrap()
-        } else {
-            let token_balance_raw = client.get_token_account_balance(&vault_mint_holder).expect("Can't get token balance for creators's pda");
-            token_balance_raw.amount.parse::<u64>().unwrap()
-        };
-
-        let instarctions = vec![Instruction::new_with_borsh(
-            program_id,
-            &SellInstruction::Withdraw{amount},
-            vec![
-                AccountMeta::new(wallet_pubkey, true),
-                AccountMeta::new(system_program::id(), false),
-                AccountMeta::new(vault_pda, false),
-
-                AccountMeta::new(vault_mint_holder, false),
-                AccountMeta::new_readonly(mint, false),
-                AccountMeta::new_readonly(spl_token::id(), false),
-                AccountMeta::new_readonly("SysvarRent111111111111111111111111111111111".parse::<Pubkey>().unwrap(), false),
-                AccountMeta::new_readonly("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL".parse::<Pubkey>().unwrap(), false),
-
-                AccountMeta::new(treasury, false),
-                AccountMeta::new(wallet_mint_holder, false),
-            ],
-        )];
-        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
-        let (recent_blockhash, _) = client.get_recent_blockhash().expect("Can't get blockhash");
-        tx.sign(&vec![&wallet_keypair], recent_blockhash);
-        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
-        println!("Success. Check transaction: {:?}",hash);
-     }
-
-    if let Some(matches) = matches.subcommand_matches("buy") {
-        let url = match matches.value_of("env"){
-            Some("dev")=>"https://api.devnet.solana.com",
-            _=>"https://api.mainnet-beta.solana.com",
-        };
-        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
-        
-        let wallet_path = matches.value_of("sign").unwrap();
-        let wallet_keypair = read_keypair_file(wallet_path).expect("Can't open file-wallet");
-        let wallet_pubkey = wallet_keypair.pubkey();
-
-        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
-        let amount = matches.value_of("amount").unwrap().parse::<u64>().unwrap();
-
-
-
-        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
-        let vault_mint_holder = spl_associated_token_account::get_associated_token_address(&vault_pda, &mint);
-        let wallet_mint_holder = spl_associated_token_account::get_associated_token_address(&wallet_pubkey, &mint);
-        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
-
-        let instarctions = vec![Instruction::new_with_borsh(
-            program_id,
-            &SellInstruction::Buy{amount},
-            vec![
-                AccountMeta::new(wallet_pubkey, true),
-                AccountMeta::new(system_program::id(), false),
-                AccountMeta::new(vault_pda, false),
-
-                AccountMeta::new(vault_mint_holder, false),
-                AccountMeta::new_readonly(mint, false),
-                AccountMeta::new_readonly(spl_token::id(), false),
-                AccountMeta::new_readonly("SysvarRent111111111111111111111111111111111".parse::<Pubkey>().unwrap(), false),
-                AccountMeta::new_readonly("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL".parse::<Pubkey>().unwrap(), false),
-
-                AccountMeta::new(treasury, false),
-                AccountMeta::new(wallet_mint_holder, false),
-                AccountMeta::new(price_address, false),
-            ],
-        )];
-        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
-        let (recent_blockhash, _) = client.get_recent_blockhash().expect("Can't get blockhash");
-        tx.sign(&vec![&wallet_keypair], recent_blockhash);
-        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
-        println!("Success. Check transaction: {:?}",hash);
-     }
-
-    if let Some(matches) = matches.subcommand_matches("show_vault_address") {
-        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
-        let (pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
-        println!("Vault account: {:?}", pda);
-    }
-
-    if let Some(matches) = matches.subcommand_matches("generate_vault_address") {
-        let url = match matches.value_of("env"){
-            Some("dev")=>"https://api.devnet.solana.com",
-            _=>"https://api.mainnet-beta.solana.com",
-        };
-        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
-        
-        let wallet_path = matches.value_of("sign").unwrap();
-        let wallet_keypair = read_keypair_file(wallet_path).expect("Can't open file-wallet");
-        let wallet_pubkey = wallet_keypair.pubkey();
-
-        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
-    
-        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
-
-        let instarctions = vec![Instruction::new_with_borsh(
-            program_id,
-            &SellInstruction::Generate,
-            vec![
-                AccountMeta::new(wallet_pubkey, true),
-                AccountMeta::new(system_program::id(), false),
-                AccountMeta::new(vault_pda, false),
-            ],
-        )];
-        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
-        let (recent_blockhash, _) = client.get_recent_blockhash().expect("Can't get blockhash");
-        tx.sign(&vec![&wallet_keypair], recent_blockhash);
-        client.send_transaction(&tx).expect("Transaction failed.");
-        println!("vault account generated: {:?}", vault_pda);
+        .subcommand(SubCommand::with_name("set_sale_end")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("end_ts")
+                .short("t")
+                .long("end_ts")
+                .help("Unix timestamp, 0 removes the end")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("burn_unsold")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("take_mint_authority")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("supply_cap")
+                .short("c")
+                .long("supply_cap")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("release_mint_authority")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("new_authority")
+                .short("n")
+                .long("new_authority")
+                .help("New mint authority, the signer by default")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("withdraw_proceeds")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("lamports")
+                .short("l")
+                .long("lamports")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("destination")
+                .short("d")
+                .long("destination")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("unwrap_proceeds")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set_split")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("recipient")
+                .short("r")
+                .long("recipient")
+                .help("<wallet>:<bps>, repeat for every recipient")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("dust")
+                .short("d")
+                .long("dust")
+                .help("Recipient of rounding dust, the first recipient by default")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("distribute_proceeds")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set_referral")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("bps")
+                .short("b")
+                .long("bps")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("referrals")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("referrer")
+                .short("r")
+                .long("referrer")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("history")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("all")
+                .short("a")
+                .long("all")
+                .help("Print every event, not only purchases")
+                .required(false)
+            )
+        )
+        .subcommand(SubCommand::with_name("set_admins")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("signer")
+                .short("a")
+                .long("signer")
+                .help("Admin signer pubkey, repeat for every signer")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("threshold")
+                .short("t")
+                .long("threshold")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set_price")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("price")
+                .short("p")
+                .long("price")
+                .help("Opening price in lamports per token; once it is set, change it with schedule_price")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("schedule_price")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("price")
+                .short("p")
+                .long("price")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("effective_ts")
+                .short("t")
+                .long("effective_ts")
+                .help("Unix timestamp the new price takes effect at")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("cancel_price_change")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("status")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set_price_bounds")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("floor")
+                .short("f")
+                .long("floor")
+                .help("Lowest price in lamports per token")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("ceiling")
+                .short("c")
+                .long("ceiling")
+                .help("Highest price in lamports per token")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("max_change")
+                .short("m")
+                .long("max_change")
+                .help("Largest change per update in bps of the current price, unlimited if omitted")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("window")
+                .short("w")
+                .long("window")
+                .help("Seconds over which --max_window_change caps the total change, no window if omitted")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("max_window_change")
+                .long("max_window_change")
+                .help("Largest total change within one window in bps of the price it opened at")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("migrate")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set_limit_target")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("target")
+                .short("t")
+                .long("target")
+                .help("payer or recipient: whose wallet caps and allowlists apply to on gifted purchases")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("distribute")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("csv")
+                .short("c")
+                .long("csv")
+                .help("wallet,amount rows to pay from the vault")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("progress")
+                .short("p")
+                .long("progress")
+                .help("File recording sent and paid rows so an interrupted run can resume, <csv>.progress by default")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("withdraw_lamports")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("lamports")
+                .short("l")
+                .long("lamports")
+                .help("Lamports to sweep, everything above the vault's rent reserve if omitted")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("destination")
+                .short("d")
+                .long("destination")
+                .help("Wallet that receives the lamports, the signer if omitted")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("grant_role")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("wallet")
+                .short("w")
+                .long("wallet")
+                .help("Wallet receiving the role")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("role")
+                .short("r")
+                .long("role")
+                .help("price, withdraw, pause or config")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("revoke_role")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("wallet")
+                .short("w")
+                .long("wallet")
+                .help("Wallet losing the role")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("role")
+                .short("r")
+                .long("role")
+                .help("price, withdraw, pause or config")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set_paused")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("paused")
+                .short("p")
+                .long("paused")
+                .help("yes to stop purchases, no to resume them")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set_voucher_signer")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("signer")
+                .short("k")
+                .long("signer")
+                .help("Key that signs vouchers, or none to stop requiring them")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("issue_voucher")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Voucher signer keypair")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("buyer")
+                .short("b")
+                .long("buyer")
+                .help("Wallet the voucher is for")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("max_amount")
+                .short("a")
+                .long("max_amount")
+                .help("Most tokens the voucher buys")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("expiry")
+                .short("x")
+                .long("expiry")
+                .help("Unix timestamp the voucher stops working at")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("nonce")
+                .short("n")
+                .long("nonce")
+                .help("Unique voucher number, the current time in nanoseconds if omitted")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("block")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("wallet")
+                .short("w")
+                .long("wallet")
+                .help("Wallet to stop buying")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("unblock")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("wallet")
+                .short("w")
+                .long("wallet")
+                .help("Wallet to allow again")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("blocklist")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set_token_gate")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("mint")
+                .short("m")
+                .long("mint")
+                .help("Mint buyers have to hold, or none to lift the gate")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("min_balance")
+                .short("b")
+                .long("min_balance")
+                .help("Least gate tokens a buyer needs, 1 by default")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("cap_bps")
+                .short("c")
+                .long("cap_bps")
+                .help("Largest purchase in bps of the gate balance, uncapped if omitted")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set_rounds")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("round")
+                .short("r")
+                .long("round")
+                .help("start,end,price,allocation[,wallet_cap[,allowlist_root]] in time order; none clears the rounds")
+                .required(false)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+        )
+        .subcommand(SubCommand::with_name("allowlist_root")
+            .arg(Arg::with_name("file")
+                .short("f")
+                .long("file")
+                .help("One wallet per line")
+                .required(true)
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("set_purchase_limits")
+            .arg(Arg::with_name("program_id")
+                .short("i")
+                .long("contract_id")
+                .required(true)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("sign")
+                .short("s")
+                .long("sign")
+                .help("Fee payer first, then any further admin co-signers")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("min")
+                .short("m")
+                .long("min")
+                .help("Smallest purchase in tokens, 0 if omitted")
+                .required(false)
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("lot")
+                .short("l")
+                .long("lot")
+                .help("Purchases have to be a multiple of this, any amount if omitted")
+                .required(false)
+                .takes_value(true)
+            )
+        )
+        .get_matches();
+
+    let mint = "5CZJ7e4uUWCogW2z7rvcE2yur6yE7Z7kcGYrFqLLXSL9".parse::<Pubkey>().unwrap();
+
+    if let Some(matches) = matches.subcommand_matches("set_price") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        
+        let price = matches.value_of("price").unwrap().parse::<u64>().expect("price should be a number");
+        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(price_address, false),
+            AccountMeta::new(system_program::id(), false),
+            AccountMeta::new_readonly("SysvarRent111111111111111111111111111111111".parse::<Pubkey>().unwrap(), false),
+            AccountMeta::new_readonly(config_address, false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetPrice{price},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+        
+    }
+
+    if let Some(matches) = matches.subcommand_matches("schedule_price") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+
+        let price = matches.value_of("price").unwrap().parse::<u64>().expect("price should be a number");
+        let effective_ts = matches.value_of("effective_ts").unwrap().parse::<i64>().expect("effective_ts should be a unix timestamp");
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(price_address, false),
+            AccountMeta::new_readonly(config_address, false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SchedulePrice{price, effective_ts},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("cancel_price_change") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(price_address, false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::CancelPriceChange,
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("set_price_bounds") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+
+        let floor = matches.value_of("floor").unwrap().parse::<u64>().expect("floor should be a number");
+        let ceiling = matches.value_of("ceiling").unwrap().parse::<u64>().expect("ceiling should be a number");
+        let max_change_bps = matches.value_of("max_change").map(|bps| bps.parse::<u16>().expect("max_change should be a number")).unwrap_or(0);
+        let window_secs = matches.value_of("window").map(|secs| secs.parse::<i64>().expect("window should be a number")).unwrap_or(0);
+        let max_window_change_bps = matches.value_of("max_window_change").map(|bps| bps.parse::<u16>().expect("max_window_change should be a number")).unwrap_or(0);
+        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(config_address, false),
+            AccountMeta::new_readonly(price_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetPriceBounds{floor, ceiling, max_change_bps, window_secs, max_window_change_bps},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("migrate") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_path = matches.value_of("sign").unwrap();
+        let wallet_keypair = read_keypair_file(wallet_path).expect("Can't open file-wallet");
+        let wallet_pubkey = wallet_keypair.pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
+        let (treasury_address, _treasury_bump) = Pubkey::find_program_address(&["treasury".as_bytes()], &program_id);
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let (state_address, _state_bump) = Pubkey::find_program_address(&["state".as_bytes()], &program_id);
+        let (admin_address, _admin_bump) = Pubkey::find_program_address(&["admin".as_bytes()], &program_id);
+
+        let outdated: Vec<Pubkey> = client.get_program_accounts(&program_id).expect("Can't get program accounts")
+            .into_iter()
+            .filter(|(address, account)|{
+                if *address==price_address{
+                    !is_current::<PriceData>(&account.data)
+                } else if *address==treasury_address{
+                    !is_current::<TreasuryData>(&account.data)
+                } else if *address==config_address{
+                    !is_current::<SaleConfig>(&account.data)
+                } else if *address==state_address{
+                    !is_current::<SaleState>(&account.data)
+                } else if *address==admin_address{
+                    !is_current::<AdminData>(&account.data)
+                } else if is_current::<BlockedWallet>(&account.data) || is_current::<RoundPurchase>(&account.data) || is_closed_vault(&account.data){
+                    false
+                } else {
+                    // Open vaults and used voucher nonces carry no data, anything else with data is a referrer PDA.
+                    !account.data.is_empty() && !is_current::<ReferrerData>(&account.data)
+                }
+            })
+            .map(|(address, _)| address)
+            .collect();
+
+        if outdated.is_empty(){
+            println!("All accounts are on the current layout");
+        }
+
+        for batch in outdated.chunks(MIGRATE_BATCH){
+            let mut accounts = vec![
+                AccountMeta::new(wallet_pubkey, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ];
+            accounts.extend(batch.iter().map(|address| AccountMeta::new(*address, false)));
+
+            let instarctions = vec![Instruction::new_with_borsh(
+                program_id,
+                &SellInstruction::Migrate,
+                accounts,
+            )];
+            let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+            let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+            tx.sign(&vec![&wallet_keypair], recent_blockhash);
+            let hash  = client.send_and_confirm_transaction(&tx).expect("Transaction failed.");
+            println!("Migrated {} accounts. Check transaction: {:?}", batch.len(), hash);
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("set_limit_target") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+
+        let target = match matches.value_of("target").unwrap(){
+            "payer"=>LimitTarget::Payer,
+            "recipient"=>LimitTarget::Recipient,
+            _=>panic!("target should be payer or recipient"),
+        };
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(config_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetLimitTarget{target},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("grant_role") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (admin_address, _admin_bump) = Pubkey::find_program_address(&["admin".as_bytes()], &program_id);
+        let wallet = matches.value_of("wallet").unwrap().parse::<Pubkey>().expect("Wrong wallet format");
+        let role = parse_role(matches.value_of("role").unwrap());
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(admin_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        for signer in wallet_keypairs.iter().skip(1){
+            accounts.push(AccountMeta::new_readonly(signer.pubkey(), true));
+        }
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::GrantRole{wallet, role},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("revoke_role") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (admin_address, _admin_bump) = Pubkey::find_program_address(&["admin".as_bytes()], &program_id);
+        let wallet = matches.value_of("wallet").unwrap().parse::<Pubkey>().expect("Wrong wallet format");
+        let role = parse_role(matches.value_of("role").unwrap());
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(admin_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        for signer in wallet_keypairs.iter().skip(1){
+            accounts.push(AccountMeta::new_readonly(signer.pubkey(), true));
+        }
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::RevokeRole{wallet, role},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("set_paused") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let paused = match matches.value_of("paused").unwrap(){
+            "yes"=>true,
+            "no"=>false,
+            _=>panic!("paused should be yes or no"),
+        };
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(config_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetPaused{paused},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("set_voucher_signer") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let signer = match matches.value_of("signer").unwrap(){
+            "none"=>Pubkey::default(),
+            signer=>signer.parse::<Pubkey>().expect("Wrong signer format"),
+        };
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(config_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetVoucherSigner{signer},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("issue_voucher") {
+        let signer_path = matches.value_of("sign").unwrap();
+        let signer_keypair = read_keypair_file(signer_path).expect("Can't open file-wallet");
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let nonce = match matches.value_of("nonce"){
+            Some(nonce)=>nonce.parse::<u64>().expect("nonce should be a number"),
+            None=>SystemTime::now().duration_since(UNIX_EPOCH).expect("Clock is before 1970").as_nanos() as u64,
+        };
+        let voucher = Voucher{
+            buyer: matches.value_of("buyer").unwrap().parse::<Pubkey>().expect("Wrong buyer format"),
+            max_amount: matches.value_of("max_amount").unwrap().parse::<u64>().expect("max_amount should be a number"),
+            expiry: matches.value_of("expiry").unwrap().parse::<i64>().expect("expiry should be a unix timestamp"),
+            nonce,
+        };
+
+        // Signing happens offline; the voucher only reaches the chain with the buyer's purchase.
+        let signature = signer_keypair.sign_message(&voucher.message(&program_id));
+        println!("{}", encode_voucher(&voucher, &signer_keypair.pubkey(), &signature));
+    }
+
+    if let Some(matches) = matches.subcommand_matches("block") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let wallet = matches.value_of("wallet").unwrap().parse::<Pubkey>().expect("Wrong wallet format");
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(blocklist_address(&program_id, &wallet), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::Block{wallet},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("unblock") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let wallet = matches.value_of("wallet").unwrap().parse::<Pubkey>().expect("Wrong wallet format");
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(blocklist_address(&program_id, &wallet), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::Unblock{wallet},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("blocklist") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+
+        let config = RpcProgramAccountsConfig{
+            filters: Some(vec![RpcFilterType::DataSize(BlockedWallet::SIZE as u64)]),
+            ..RpcProgramAccountsConfig::default()
+        };
+        let mut entries: Vec<BlockedWallet> = client.get_program_accounts_with_config(&program_id, config)
+            .expect("Can't get blocklist accounts")
+            .iter()
+            .filter_map(|(_, account)| unpack_account::<BlockedWallet>(&account.data).ok())
+            .collect();
+        entries.sort_by_key(|entry| entry.blocked_at);
+
+        println!("{:<44} {:>20}", "wallet", "blocked at");
+        for entry in entries.iter(){
+            println!("{:<44} {:>20}", entry.wallet.to_string(), entry.blocked_at);
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("set_token_gate") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let gate_mint = match matches.value_of("mint").unwrap(){
+            "none"=>Pubkey::default(),
+            gate_mint=>gate_mint.parse::<Pubkey>().expect("Wrong mint format"),
+        };
+        let min_balance = matches.value_of("min_balance").unwrap_or("1").parse::<u64>().expect("min_balance should be a number");
+        let cap_bps = matches.value_of("cap_bps").unwrap_or("0").parse::<u32>().expect("cap_bps should be a number");
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(config_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetTokenGate{mint: gate_mint, min_balance, cap_bps},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("set_rounds") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
+        let rounds: Vec<SaleRound> = matches.values_of("round")
+            .map(|rounds| rounds.map(parse_round).collect())
+            .unwrap_or_default();
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new_readonly(config_address, false),
+            AccountMeta::new_readonly(price_address, false),
+            AccountMeta::new(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetRounds{rounds},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("allowlist_root") {
+        let wallets = read_wallets(matches.value_of("file").unwrap());
+        let (root, _) = allowlist_proof(&wallets, &Pubkey::default());
+        println!("{} ({} wallets)", to_hex(&root), wallets.len());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("set_purchase_limits") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let min_purchase = matches.value_of("min").unwrap_or("0").parse::<u64>().expect("min should be a number");
+        let lot_size = matches.value_of("lot").unwrap_or("0").parse::<u64>().expect("lot should be a number");
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(config_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetPurchaseLimits{min_purchase, lot_size},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("status") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
+
+        let data = client.get_account_data(&price_address).expect("Price is not set yet");
+        let mut price_data: PriceData = unpack_account(&data).expect("Can't parse price account");
+
+        // Buy applies a due change lazily, so show what the next purchase would actually pay.
+        let now = client.get_block_time(client.get_slot().expect("Can't get slot")).expect("Can't get cluster time");
+        price_data.apply_pending(now);
+
+        println!("current price: {} lamports per token", price_data.price);
+        if price_data.pending_price>0{
+            println!("pending price: {} lamports per token from {} ({} seconds from now)", price_data.pending_price, price_data.pending_ts, price_data.pending_ts-now);
+        } else {
+            println!("pending price: none");
+        }
+
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let config: SaleConfig = client.get_account_data(&config_address).ok()
+            .map(|data| unpack_account(&data).expect("Can't parse config account"))
+            .unwrap_or_default();
+        if config.price_ceiling>0{
+            println!("price bounds: {} to {}, at most {} bps per update", config.price_floor, config.price_ceiling, config.max_price_change_bps);
+            if config.price_window>0{
+                println!("price window: at most {} bps per {} seconds, from {} lamports since {}", config.max_window_change_bps, config.price_window, price_data.window_price, price_data.window_start);
+            }
+        } else {
+            println!("price bounds: none");
+        }
+        println!("purchases: {}", if config.paused { "paused" } else { "open" });
+        print!("purchase size: at least {} tokens", config.min_purchase.max(1));
+        if config.lot_size>1{
+            print!(", in lots of {}", config.lot_size);
+        }
+        println!();
+        if config.voucher_signer!=Pubkey::default(){
+            println!("vouchers: required, signed by {}", config.voucher_signer);
+        } else {
+            println!("vouchers: not required");
+        }
+        if config.gate_mint!=Pubkey::default(){
+            print!("token gate: at least {} of {}", config.gate_min_balance, config.gate_mint);
+            if config.gate_cap_bps>0{
+                print!(", buying at most {} bps of that balance at once", config.gate_cap_bps);
+            }
+            println!();
+        } else {
+            println!("token gate: none");
+        }
+
+        let (state_address, _state_bump) = Pubkey::find_program_address(&["state".as_bytes()], &program_id);
+        if let Ok(data) = client.get_account_data(&state_address){
+            let state: SaleState = unpack_account(&data).expect("Can't parse state account");
+            let active = state.active_round(now);
+            for (index, round) in state.rounds.iter().enumerate(){
+                let phase = if active==Some(index){
+                    "open"
+                } else if now<round.start_ts{
+                    "upcoming"
+                } else {
+                    "over"
+                };
+                print!(
+                    "round {} ({}): {} to {}, {} lamports per token, {} of {} sold ({}%)",
+                    index, phase, round.start_ts, round.end_ts, round.price,
+                    round.sold, round.allocation, round.sold as u128 * 100 / round.allocation as u128,
+                );
+                if round.wallet_cap>0{
+                    print!(", at most {} per wallet", round.wallet_cap);
+                }
+                if round.allowlist_root!=[0; 32]{
+                    print!(", allowlist {}", to_hex(&round.allowlist_root));
+                }
+                println!();
+            }
+            if !state.rounds.is_empty() && active.is_none(){
+                println!("no round is open, purchases are closed");
+            }
+        }
+
+        let (admin_address, _admin_bump) = Pubkey::find_program_address(&["admin".as_bytes()], &program_id);
+        if let Ok(data) = client.get_account_data(&admin_address){
+            let admins: AdminData = unpack_account(&data).expect("Can't parse admin account");
+            for grant in admins.roles.iter(){
+                let roles: Vec<String> = [Role::Price, Role::Withdraw, Role::Pause, Role::Config].iter()
+                    .filter(|role| grant.roles & role.bit()!=0)
+                    .map(|role| format!("{:?}", role).to_lowercase())
+                    .collect();
+                println!("role holder: {} ({})", grant.wallet, roles.join(", "));
+            }
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("set_split") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+
+        let recipients: Vec<SplitShare> = matches.values_of("recipient").unwrap().map(|recipient|{
+            let mut parts = recipient.splitn(2, ':');
+            let wallet = parts.next().unwrap().parse::<Pubkey>().expect("Wrong recipient format");
+            let bps = parts.next().expect("Recipient should be <wallet>:<bps>").parse::<u16>().expect("bps should be a number");
+            SplitShare{wallet, bps}
+        }).collect();
+
+        let dust_recipient = if let Some(dust_str)=matches.value_of("dust"){
+            dust_str.parse::<Pubkey>().expect("Wrong dust recipient format")
+        } else {
+            recipients[0].wallet
+        };
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(config_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetSplit{recipients, dust_recipient},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("distribute_proceeds") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_path = matches.value_of("sign").unwrap();
+        let wallet_keypair = read_keypair_file(wallet_path).expect("Can't open file-wallet");
+        let wallet_pubkey = wallet_keypair.pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (treasury, _treasury_bump) = Pubkey::find_program_address(&["treasury".as_bytes()], &program_id);
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+
+        let config_data = client.get_account_data(&config_address).expect("Can't get config account");
+        let config: SaleConfig = unpack_account(&config_data).expect("Can't parse config account");
+
+        let mut accounts = vec![
+            AccountMeta::new(treasury, false),
+            AccountMeta::new_readonly(config_address, false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        for share in config.split.iter(){
+            accounts.push(AccountMeta::new(share.wallet, false));
+        }
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::DistributeProceeds,
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&vec![&wallet_keypair], recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("set_referral") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let bps = matches.value_of("bps").unwrap().parse::<u16>().expect("bps should be a number");
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(config_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetReferralBps{bps},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("referrals") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+
+        let referrers: Vec<ReferrerData> = if let Some(referrer_str) = matches.value_of("referrer"){
+            let referrer = referrer_str.parse::<Pubkey>().expect("Wrong referrer format");
+            let (referrer_address, _referrer_bump) = Pubkey::find_program_address(&["referrer".as_bytes(), referrer.as_ref()], &program_id);
+            let data = client.get_account_data(&referrer_address).expect("Referrer has no referrals yet");
+            vec![unpack_account::<ReferrerData>(&data).expect("Can't parse referrer account")]
+        } else {
+            let config = RpcProgramAccountsConfig{
+                filters: Some(vec![RpcFilterType::DataSize(ReferrerData::SIZE as u64)]),
+                ..RpcProgramAccountsConfig::default()
+            };
+            client.get_program_accounts_with_config(&program_id, config)
+                .expect("Can't get referrer accounts")
+                .iter()
+                .filter_map(|(_, account)| unpack_account::<ReferrerData>(&account.data).ok())
+                .collect()
+        };
+
+        println!("{:<44} {:>10} {:>20} {:>20}", "referrer", "purchases", "tokens sold", "lamports earned");
+        for referrer in referrers.iter(){
+            println!("{:<44} {:>10} {:>20} {:>20}", referrer.referrer.to_string(), referrer.purchases, referrer.tokens_sold, referrer.lamports_earned);
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("history") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let print_all = matches.is_present("all");
+
+        // The node answers a page (1000 at most) at a time, newest first: walk back until it runs out.
+        let mut signatures = Vec::new();
+        let mut before = None;
+        loop{
+            let config = GetConfirmedSignaturesForAddress2Config{before, ..GetConfirmedSignaturesForAddress2Config::default()};
+            let page = client.get_signatures_for_address_with_config(&program_id, config).expect("Can't get program signatures");
+            match page.last(){
+                Some(oldest)=>before = Some(oldest.signature.parse::<Signature>().expect("Wrong signature format")),
+                None=>break,
+            }
+            signatures.extend(page);
+        }
+        for status in signatures.iter().rev(){
+            if status.err.is_some(){
+                continue;
+            }
+            let signature = status.signature.parse::<Signature>().expect("Wrong signature format");
+            let transaction = client.get_transaction(&signature, UiTransactionEncoding::Json).expect("Can't get transaction");
+            let logs: Option<Vec<String>> = transaction.transaction.meta.and_then(|meta| meta.log_messages);
+
+            for record in logs.unwrap_or_default().iter().filter_map(|log| decode_event(log)){
+                match record.event{
+                    SellEvent::Bought{buyer, amount, lamports, price, recipient, ..}=>{
+                        if recipient==buyer{
+                            println!("slot {} buy: {} bought {} tokens for {} lamports at {} ({})", record.slot, buyer, amount, lamports, price, signature);
+                        } else {
+                            println!("slot {} buy: {} bought {} tokens for {} for {} lamports at {} ({})", record.slot, buyer, amount, recipient, lamports, price, signature);
+                        }
+                    }
+                    event=>{
+                        if print_all{
+                            println!("slot {} {:?} ({})", record.slot, event, signature);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("set_admins") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (admin_address, _admin_bump) = Pubkey::find_program_address(&["admin".as_bytes()], &program_id);
+
+        let signers: Vec<Pubkey> = matches.values_of("signer").unwrap()
+            .map(|signer| signer.parse::<Pubkey>().expect("Wrong signer format"))
+            .collect();
+        let threshold = matches.value_of("threshold").unwrap().parse::<u8>().expect("threshold should be a number");
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(admin_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        for keypair in wallet_keypairs.iter().skip(1){
+            accounts.push(AccountMeta::new_readonly(keypair.pubkey(), true));
+        }
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetAdmins{signers, threshold},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("withdraw_proceeds") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (treasury, _treasury_bump) = Pubkey::find_program_address(&["treasury".as_bytes()], &program_id);
+
+        let destination = if let Some(destination_str)=matches.value_of("destination"){
+            destination_str.parse::<Pubkey>().expect("Wrong destination format")
+        } else {
+            wallet_pubkey
+        };
+
+        let lamports = if let Some(lamports_str)=matches.value_of("lamports"){
+            lamports_str.parse::<u64>().expect("lamports should be a number")
+        } else {
+            let treasury_account = client.get_account(&treasury).expect("Can't get treasury account");
+            let rent_reserve = client.get_minimum_balance_for_rent_exemption(treasury_account.data.len()).expect("Can't get rent");
+            treasury_account.lamports.saturating_sub(rent_reserve)
+        };
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(treasury, false),
+            AccountMeta::new(destination, false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::WithdrawProceeds{lamports, destination},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("unwrap_proceeds") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (treasury, _treasury_bump) = Pubkey::find_program_address(&["treasury".as_bytes()], &program_id);
+        let treasury_wsol = treasury_wsol_address(&treasury);
+
+        let (unwrap_address, _unwrap_bump) = Pubkey::find_program_address(&["unwrap".as_bytes()], &program_id);
+
+        let balance = client.get_token_account_balance(&treasury_wsol).expect("Treasury has no wSOL account");
+        println!("Unwrapping {} wSOL", balance.ui_amount_string);
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(treasury, false),
+            AccountMeta::new(treasury_wsol, false),
+            AccountMeta::new(unwrap_address, false),
+            AccountMeta::new_readonly(spl_token::native_mint::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly("SysvarRent111111111111111111111111111111111".parse::<Pubkey>().unwrap(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::UnwrapProceeds,
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("distribute") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let csv_path = matches.value_of("csv").unwrap();
+        let progress_path = matches.value_of("progress")
+            .map(|path| path.to_string())
+            .unwrap_or_else(|| format!("{}.progress", csv_path));
+
+        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+        let vault_mint_holder = spl_associated_token_account::get_associated_token_address(&vault_pda, &mint);
+        let (admin_address, _admin_bump) = Pubkey::find_program_address(&["admin".as_bytes()], &program_id);
+
+        let mut progress = read_progress(&progress_path);
+
+        // A batch sent without a confirmation may have landed anyway. Until its blockhash expires it
+        // still can, so only once that has happened does a missing status mean its rows are unpaid.
+        let unsettled: HashMap<Signature, Hash> = progress.values()
+            .filter_map(|row| row.pending.map(|blockhash| (row.signature, blockhash)))
+            .collect();
+        for (signature, blockhash) in unsettled{
+            let expired = !client.is_blockhash_valid(&blockhash, CommitmentConfig::processed()).expect("Can't check blockhash");
+            let status = client.get_signature_statuses_with_history(&[signature]).expect("Can't get signature status").value.remove(0);
+            let landed = match status{
+                Some(status)=>status.err.is_none(),
+                None if expired=>false,
+                None=>panic!("Transaction {} may still land, rerun once its blockhash has expired", signature),
+            };
+            let mut batch: Vec<(usize, Payout)> = progress.iter()
+                .filter(|(_, row)| row.signature==signature)
+                .map(|(row, progress)| (*row, progress.payout.clone()))
+                .collect();
+            batch.sort_by_key(|(row, _)| *row);
+            if landed{
+                record_progress(&progress_path, &batch, &signature, None);
+                for (row, _) in batch.iter(){
+                    progress.get_mut(row).unwrap().pending = None;
+                }
+                println!("Transaction {} landed, rows {} to {} are paid", signature, batch[0].0, batch[batch.len() - 1].0);
+            } else {
+                for (row, _) in batch.iter(){
+                    progress.remove(row);
+                }
+                println!("Transaction {} never landed, rows {} to {} will be sent again", signature, batch[0].0, batch[batch.len() - 1].0);
+            }
+        }
+
+        let mut pending = Vec::new();
+        for (row, payout) in read_payouts(csv_path){
+            match progress.get(&row){
+                Some(done) if done.payout==payout=>continue,
+                Some(_)=>panic!("Row {} differs from what {} says was paid, refusing to continue", row, progress_path),
+                None=>pending.push((row, payout)),
+            }
+        }
+        println!("{} rows already paid, {} to go", progress.len(), pending.len());
+
+        for batch in pending.chunks(DISTRIBUTE_BATCH){
+            let mut accounts = vec![
+                AccountMeta::new(wallet_pubkey, true),
+                AccountMeta::new(system_program::id(), false),
+                AccountMeta::new_readonly(vault_pda, false),
+
+                AccountMeta::new(vault_mint_holder, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly("SysvarRent111111111111111111111111111111111".parse::<Pubkey>().unwrap(), false),
+                AccountMeta::new_readonly("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL".parse::<Pubkey>().unwrap(), false),
+
+                AccountMeta::new(state_address(&program_id), false),
+                AccountMeta::new_readonly(admin_address, false),
+            ];
+            // The payouts come straight after the admin PDA, so co-signers go last here.
+            for (_, payout) in batch.iter(){
+                accounts.push(AccountMeta::new_readonly(payout.recipient, false));
+                accounts.push(AccountMeta::new(spl_associated_token_account::get_associated_token_address(&payout.recipient, &mint), false));
+            }
+            for signer in wallet_keypairs.iter().skip(1){
+                accounts.push(AccountMeta::new_readonly(signer.pubkey(), true));
+            }
+
+            let payouts = batch.iter().map(|(_, payout)| payout.clone()).collect();
+            let instarctions = vec![Instruction::new_with_borsh(
+                program_id,
+                &SellInstruction::Distribute{payouts},
+                accounts,
+            )];
+            let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+            let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+            tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+
+            // Recorded before sending: if confirming times out, the next run looks the batch up instead of paying it twice.
+            record_progress(&progress_path, batch, &tx.signatures[0], Some(&recent_blockhash));
+            let hash = client.send_and_confirm_transaction(&tx)
+                .unwrap_or_else(|err| panic!("Transaction {} failed: {}. Rerun to settle it before anything is sent again.", tx.signatures[0], err));
+            record_progress(&progress_path, batch, &hash, None);
+            println!("Paid rows {} to {}. Check transaction: {:?}", batch[0].0, batch[batch.len() - 1].0, hash);
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("deposit") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let amount = matches.value_of("amount").unwrap().parse::<u64>().expect("amount should be a number");
+
+        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+        let vault_mint_holder = spl_associated_token_account::get_associated_token_address(&vault_pda, &mint);
+        let wallet_mint_holder = spl_associated_token_account::get_associated_token_address(&wallet_pubkey, &mint);
+        let (state_address, _state_bump) = Pubkey::find_program_address(&["state".as_bytes()], &program_id);
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(system_program::id(), false),
+            AccountMeta::new_readonly(vault_pda, false),
+
+            AccountMeta::new(vault_mint_holder, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly("SysvarRent111111111111111111111111111111111".parse::<Pubkey>().unwrap(), false),
+            AccountMeta::new_readonly("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL".parse::<Pubkey>().unwrap(), false),
+
+            AccountMeta::new(wallet_mint_holder, false),
+            AccountMeta::new(state_address, false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::Deposit{amount},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("close_sale") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+
+        let destination = if let Some(destination_str)=matches.value_of("destination"){
+            destination_str.parse::<Pubkey>().expect("Wrong destination format")
+        } else {
+            wallet_pubkey
+        };
+
+        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+        let vault_mint_holder = spl_associated_token_account::get_associated_token_address(&vault_pda, &mint);
+        let destination_mint_holder = spl_associated_token_account::get_associated_token_address(&destination, &mint);
+        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
+        let (state_address, _state_bump) = Pubkey::find_program_address(&["state".as_bytes()], &program_id);
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(system_program::id(), false),
+            AccountMeta::new(vault_pda, false),
+
+            AccountMeta::new(vault_mint_holder, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly("SysvarRent111111111111111111111111111111111".parse::<Pubkey>().unwrap(), false),
+            AccountMeta::new_readonly("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL".parse::<Pubkey>().unwrap(), false),
+
+            AccountMeta::new(destination, false),
+            AccountMeta::new(destination_mint_holder, false),
+            AccountMeta::new(price_address, false),
+            AccountMeta::new(state_address, false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::CloseSale{destination},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("set_sale_end") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let end_ts = matches.value_of("end_ts").unwrap().parse::<i64>().expect("end_ts should be a number");
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(config_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::SetSaleEnd{end_ts},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("burn_unsold") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_path = matches.value_of("sign").unwrap();
+        let wallet_keypair = read_keypair_file(wallet_path).expect("Can't open file-wallet");
+        let wallet_pubkey = wallet_keypair.pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+
+        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+        let vault_mint_holder = spl_associated_token_account::get_associated_token_address(&vault_pda, &mint);
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let (state_address, _state_bump) = Pubkey::find_program_address(&["state".as_bytes()], &program_id);
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::BurnUnsold,
+            vec![
+                AccountMeta::new_readonly(vault_pda, false),
+                AccountMeta::new(vault_mint_holder, false),
+                AccountMeta::new(mint, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(config_address, false),
+                AccountMeta::new(state_address, false),
+            ],
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&vec![&wallet_keypair], recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("take_mint_authority") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let supply_cap = matches.value_of("supply_cap").unwrap().parse::<u64>().expect("supply_cap should be a number");
+
+        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new_readonly(vault_pda, false),
+            AccountMeta::new(mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(config_address, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::TakeMintAuthority{supply_cap},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("release_mint_authority") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+
+        let new_authority = if let Some(authority_str)=matches.value_of("new_authority"){
+            authority_str.parse::<Pubkey>().expect("Wrong new_authority format")
+        } else {
+            wallet_pubkey
+        };
+
+        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new_readonly(vault_pda, false),
+            AccountMeta::new(mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(config_address, false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::ReleaseMintAuthority{new_authority},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("withdraw") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        
+        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+        let vault_mint_holder = spl_associated_token_account::get_associated_token_address(&vault_pda, &mint);
+        let destination = matches.value_of("destination")
+            .map(|destination| destination.parse::<Pubkey>().expect("Wrong destination format"))
+            .unwrap_or(wallet_pubkey);
+        let destination_mint_holder = spl_associated_token_account::get_associated_token_address(&destination, &mint);
+
+        let (state_address, _state_bump) = Pubkey::find_program_address(&["state".as_bytes()], &program_id);
+
+        let amount = if let Some(amount_str)=matches.value_of("amount"){
+            amount_str.parse::<u64>().unwrap()
+        } else {
+            let token_balance_raw = client.get_token_account_balance(&vault_mint_holder).expect("Can't get token balance for creators's pda");
+            token_balance_raw.amount.parse::<u64>().unwrap()
+        };
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(system_program::id(), false),
+            AccountMeta::new(vault_pda, false),
+
+            AccountMeta::new(vault_mint_holder, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly("SysvarRent111111111111111111111111111111111".parse::<Pubkey>().unwrap(), false),
+            AccountMeta::new_readonly("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL".parse::<Pubkey>().unwrap(), false),
+
+            AccountMeta::new_readonly(destination, false),
+            AccountMeta::new(destination_mint_holder, false),
+            AccountMeta::new(state_address, false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::Withdraw{amount, destination},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+     }
+
+    if let Some(matches) = matches.subcommand_matches("withdraw_lamports") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+        let destination = matches.value_of("destination")
+            .map(|destination| destination.parse::<Pubkey>().expect("Wrong destination format"))
+            .unwrap_or(wallet_pubkey);
+
+        let lamports = if let Some(lamports_str)=matches.value_of("lamports"){
+            lamports_str.parse::<u64>().expect("lamports should be a number")
+        } else {
+            let balance = client.get_balance(&vault_pda).expect("Can't get vault balance");
+            let rent_reserve = client.get_minimum_balance_for_rent_exemption(0).expect("Can't get rent").max(1);
+            balance.saturating_sub(rent_reserve)
+        };
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(vault_pda, false),
+            AccountMeta::new(destination, false),
+            AccountMeta::new_readonly(state_address(&program_id), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::WithdrawLamports{lamports, destination},
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("buy") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_path = matches.value_of("sign").unwrap();
+        let wallet_keypair = read_keypair_file(wallet_path).expect("Can't open file-wallet");
+        let wallet_pubkey = wallet_keypair.pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let amount = matches.value_of("amount").unwrap().parse::<u64>().unwrap();
+        let fill_mode = match matches.value_of("fill"){
+            None | Some("all")=>FillMode::AllOrNothing,
+            Some("partial")=>FillMode::Partial,
+            _=>panic!("fill should be all or partial"),
+        };
+        let wrap = matches.is_present("wrap");
+        let payment = if wrap || matches.is_present("wsol") { PaymentSource::WrappedSol } else { PaymentSource::Lamports };
+        let recipient = matches.value_of("recipient")
+            .map(|recipient| recipient.parse::<Pubkey>().expect("Wrong recipient format"))
+            .unwrap_or(wallet_pubkey);
+
+        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+        let vault_mint_holder = spl_associated_token_account::get_associated_token_address(&vault_pda, &mint);
+        let recipient_mint_holder = spl_associated_token_account::get_associated_token_address(&recipient, &mint);
+        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
+        let (treasury, _treasury_bump) = Pubkey::find_program_address(&["treasury".as_bytes()], &program_id);
+        let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], &program_id);
+        let (state_address, _state_bump) = Pubkey::find_program_address(&["state".as_bytes()], &program_id);
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(system_program::id(), false),
+            AccountMeta::new(vault_pda, false),
+
+            AccountMeta::new(vault_mint_holder, false),
+            AccountMeta::new(mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly("SysvarRent111111111111111111111111111111111".parse::<Pubkey>().unwrap(), false),
+            AccountMeta::new_readonly("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL".parse::<Pubkey>().unwrap(), false),
+
+            AccountMeta::new(treasury, false),
+            AccountMeta::new_readonly(recipient, false),
+            AccountMeta::new(recipient_mint_holder, false),
+            AccountMeta::new(price_address, false),
+            AccountMeta::new_readonly(config_address, false),
+            AccountMeta::new(state_address, false),
+            AccountMeta::new_readonly(blocklist_address(&program_id, &wallet_pubkey), false),
+            AccountMeta::new_readonly(blocklist_address(&program_id, &recipient), false),
+        ];
+        let mut blocklist_bumps = [blocklist_bump(&program_id, &wallet_pubkey), blocklist_bump(&program_id, &recipient), 0];
+
+        let config: SaleConfig = client.get_account_data(&config_address).ok()
+            .map(|data| unpack_account(&data).expect("Can't parse config account"))
+            .unwrap_or_default();
+        if config.gate_mint!=Pubkey::default(){
+            let gate_account = match matches.value_of("gate_account"){
+                Some(gate_account)=>gate_account.parse::<Pubkey>().expect("Wrong gate account format"),
+                None=>spl_associated_token_account::get_associated_token_address(
+                    config.limited_wallet(&wallet_pubkey, &recipient),
+                    &config.gate_mint,
+                ),
+            };
+            accounts.push(AccountMeta::new_readonly(gate_account, false));
+        }
+
+        let mut instarctions = Vec::new();
+        if let Some(voucher_str) = matches.value_of("voucher"){
+            let (voucher, signer, signature) = parse_voucher(voucher_str);
+            let (nonce_address, _nonce_bump) = Pubkey::find_program_address(
+                &["voucher".as_bytes(), voucher.buyer.as_ref(), &voucher.nonce.to_le_bytes()],
+                &program_id,
+            );
+            instarctions.push(ed25519_instruction(&signer, &signature, &voucher.message(&program_id)));
+            accounts.push(AccountMeta::new_readonly(solana_sdk::sysvar::instructions::id(), false));
+            accounts.push(AccountMeta::new(nonce_address, false));
+        }
+
+        let state: SaleState = unpack_account(&client.get_account_data(&state_address).expect("Sale isn't generated"))
+            .expect("Can't parse state account");
+        let now = client.get_block_time(client.get_slot().expect("Can't get slot")).expect("Can't get cluster time");
+        let mut proof = Vec::new();
+        let active_round = state.active_round(now);
+        if let Some(index) = active_round{
+            let round = &state.rounds[index];
+            let buyer = *config.limited_wallet(&wallet_pubkey, &recipient);
+            if round.wallet_cap>0{
+                let (purchase_address, _purchase_bump) = Pubkey::find_program_address(
+                    &["round".as_bytes(), &[index as u8], buyer.as_ref()],
+                    &program_id,
+                );
+                accounts.push(AccountMeta::new(purchase_address, false));
+            }
+            if round.allowlist_root!=[0; 32]{
+                let allowlist_path = matches.value_of("allowlist")
+                    .unwrap_or_else(|| panic!("Round {} has an allowlist, pass it with --allowlist", index));
+                let (root, buyer_proof) = allowlist_proof(&read_wallets(allowlist_path), &buyer);
+                if root!=round.allowlist_root{
+                    panic!("{} doesn't match the round's allowlist root", allowlist_path);
+                }
+                proof = buyer_proof.expect("Buyer isn't on the round's allowlist");
+            }
+        }
+        // Only the signer's own wSOL ATA is created here, with --wrap. The treasury's comes from
+        // generate_vault_address and the referrer's has to exist already, so buyers don't pay their rent.
+        let native_mint = spl_token::native_mint::id();
+        let account_exists = |address: &Pubkey| client.get_account_with_commitment(address, CommitmentConfig::confirmed())
+            .expect("Can't get account")
+            .value
+            .is_some();
+        if payment==PaymentSource::WrappedSol{
+            let payer_wsol = spl_associated_token_account::get_associated_token_address(&wallet_pubkey, &native_mint);
+            let treasury_wsol = treasury_wsol_address(&treasury);
+            if !account_exists(&treasury_wsol){
+                panic!("Treasury has no wSOL account, run generate_vault_address first");
+            }
+
+            let balance = client.get_token_account_balance(&payer_wsol).ok()
+                .map(|balance| balance.amount.parse::<u64>().expect("Can't parse wSOL balance"));
+            if wrap{
+                let price = match active_round{
+                    Some(index)=>state.rounds[index].price,
+                    None=>{
+                        let mut price_data: PriceData = unpack_account(&client.get_account_data(&price_address).expect("Price isn't set"))
+                            .expect("Can't parse price account");
+                        price_data.apply_pending(now);
+                        price_data.price
+                    }
+                };
+                // A partial fill charges less, so wrapping for the full request always covers it.
+                let needed = price.checked_mul(amount).expect("Purchase costs more lamports than exist");
+                if balance.is_none(){
+                    instarctions.push(spl_associated_token_account::instruction::create_associated_token_account(&wallet_pubkey, &wallet_pubkey, &native_mint));
+                }
+                let shortfall = needed.saturating_sub(balance.unwrap_or(0));
+                if shortfall>0{
+                    println!("Wrapping {} lamports", shortfall);
+                    instarctions.push(system_instruction::transfer(&wallet_pubkey, &payer_wsol, shortfall));
+                    instarctions.push(spl_token::instruction::sync_native(&spl_token::id(), &payer_wsol).expect("Can't build sync_native"));
+                }
+            } else if balance.is_none(){
+                panic!("{} has no wSOL account, pass --wrap to create and fund it", wallet_pubkey);
+            }
+
+            accounts.push(AccountMeta::new(payer_wsol, false));
+            accounts.push(AccountMeta::new(treasury_wsol, false));
+        }
+
+        if let Some(referrer_str) = matches.value_of("referrer"){
+            let referrer = referrer_str.parse::<Pubkey>().expect("Wrong referrer format");
+            let (referrer_address, _referrer_bump) = Pubkey::find_program_address(&["referrer".as_bytes(), referrer.as_ref()], &program_id);
+            accounts.push(AccountMeta::new(referrer, false));
+            accounts.push(AccountMeta::new(referrer_address, false));
+            accounts.push(AccountMeta::new_readonly(blocklist_address(&program_id, &referrer), false));
+            blocklist_bumps[2] = blocklist_bump(&program_id, &referrer);
+            if payment==PaymentSource::WrappedSol{
+                let referrer_wsol = spl_associated_token_account::get_associated_token_address(&referrer, &native_mint);
+                if !account_exists(&referrer_wsol){
+                    panic!("Referrer {} has no wSOL account to take the commission", referrer);
+                }
+                accounts.push(AccountMeta::new(referrer_wsol, false));
+            }
+        }
+
+        instarctions.push(Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::Buy{amount, proof, fill_mode, payment, blocklist_bumps},
+            accounts,
+        ));
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&vec![&wallet_keypair], recent_blockhash);
+        let hash  = client.send_transaction(&tx).expect("Transaction failed.");
+        println!("Success. Check transaction: {:?}",hash);
+     }
+
+    if let Some(matches) = matches.subcommand_matches("show_vault_address") {
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+        let (pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+        println!("Vault account: {:?}", pda);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("generate_vault_address") {
+        let url = match matches.value_of("env"){
+            Some("dev")=>"https://api.devnet.solana.com",
+            _=>"https://api.mainnet-beta.solana.com",
+        };
+        let client = RpcClient::new_with_commitment(url.to_string(),CommitmentConfig::confirmed());
+        
+        let wallet_keypairs: Vec<Keypair> = matches.values_of("sign").unwrap()
+            .map(|wallet_path| read_keypair_file(wallet_path).expect("Can't open file-wallet"))
+            .collect();
+        let wallet_pubkey = wallet_keypairs[0].pubkey();
+
+        let program_id = matches.value_of("program_id").unwrap().parse::<Pubkey>().expect("Wrong contract id format");
+    
+        let (vault_pda, _) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+        let (treasury, _treasury_bump) = Pubkey::find_program_address(&["treasury".as_bytes()], &program_id);
+        let vault_mint_holder = spl_associated_token_account::get_associated_token_address(&vault_pda, &mint);
+        let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
+        let (state_address, _state_bump) = Pubkey::find_program_address(&["state".as_bytes()], &program_id);
+        let treasury_wsol = treasury_wsol_address(&treasury);
+
+        let vault_closed = matches!(client.get_account_data(&vault_pda), Ok(data) if is_closed_vault(&data));
+        if vault_closed{
+            println!("Sale was closed, its vault can't be generated again: {:?}", vault_pda);
+            return;
+        }
+
+        let pieces = [
+            ("vault account", vault_pda, program_id),
+            ("treasury account", treasury, program_id),
+            ("vault token account", vault_mint_holder, spl_token::id()),
+            ("price account", price_address, program_id),
+            ("state account", state_address, program_id),
+            ("treasury wSOL account", treasury_wsol, spl_token::id()),
+        ];
+        let existing: Vec<bool> = pieces.iter().map(|(_, address, owner)|{
+            let account = client.get_account_with_commitment(address, CommitmentConfig::confirmed())
+                .expect("Can't get account")
+                .value;
+            matches!(account, Some(account) if account.owner==*owner)
+        }).collect();
+
+        if existing.iter().all(|exists| *exists){
+            for (name, address, _) in pieces.iter(){
+                println!("{} already existed: {:?}", name, address);
+            }
+            return;
+        }
+
+        let mut accounts = vec![
+            AccountMeta::new(wallet_pubkey, true),
+            AccountMeta::new(system_program::id(), false),
+            AccountMeta::new(vault_pda, false),
+            AccountMeta::new(treasury, false),
+
+            AccountMeta::new(vault_mint_holder, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly("SysvarRent111111111111111111111111111111111".parse::<Pubkey>().unwrap(), false),
+            AccountMeta::new_readonly("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL".parse::<Pubkey>().unwrap(), false),
+
+            AccountMeta::new(price_address, false),
+            AccountMeta::new(state_address, false),
+            AccountMeta::new(treasury_wsol, false),
+            AccountMeta::new_readonly(spl_token::native_mint::id(), false),
+        ];
+        accounts.extend(admin_metas(&program_id, &wallet_keypairs));
+
+        let instarctions = vec![Instruction::new_with_borsh(
+            program_id,
+            &SellInstruction::Generate,
+            accounts,
+        )];
+        let mut tx = Transaction::new_with_payer(&instarctions, Some(&wallet_pubkey));
+        let recent_blockhash = client.get_latest_blockhash().expect("Can't get blockhash");
+        tx.sign(&wallet_keypairs.iter().collect::<Vec<_>>(), recent_blockhash);
+        let hash = client.send_and_confirm_transaction(&tx).expect("Transaction failed.");
+        for ((name, address, _), exists) in pieces.iter().zip(existing.iter()){
+            if *exists{
+                println!("{} already existed: {:?}", name, address);
+            } else {
+                println!("{} generated: {:?}", name, address);
+            }
+        }
+        println!("Success. Check transaction: {:?}",hash);
     }
 
 }
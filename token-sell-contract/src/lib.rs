@@ -1,245 +1,3118 @@
 use solana_program::program::{invoke_signed, invoke};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    entrypoint,
     entrypoint::ProgramResult,
     msg,
     program_error::ProgramError,
+    program_pack::Pack,
     pubkey::Pubkey,
     system_instruction,
-    sysvar::{Sysvar, rent::Rent},
+    sysvar::{self, Sysvar, clock::Clock, rent::Rent},
+    ed25519_program,
+    hash::hashv,
 };
 use solana_program::borsh::try_from_slice_unchecked;
 use borsh::{BorshDeserialize, BorshSerialize,BorshSchema};
-use spl_token;
-use spl_associated_token_account;
 
 // Declare and export the program's entrypoint
-entrypoint!(process_instruction);
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
 
 #[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
-enum SellInstruction{
+pub enum SellInstruction{
     Generate,
     Buy{
         #[allow(dead_code)]
         amount:u64,
+        /// Allowlist proof for the active round; empty when it has no allowlist.
+        #[allow(dead_code)]
+        proof:Vec<[u8; 32]>,
+        #[allow(dead_code)]
+        fill_mode:FillMode,
+        #[allow(dead_code)]
+        payment:PaymentSource,
+        /// Canonical bumps of the payer's, the recipient's and the referrer's ["blocked", wallet]
+        /// PDAs; the last one is ignored without a referrer.
+        #[allow(dead_code)]
+        blocklist_bumps:[u8; 3],
     },
     Withdraw{
         #[allow(dead_code)]
         amount:u64,
+        #[allow(dead_code)]
+        destination:Pubkey,
     },
     SetPrice{
         #[allow(dead_code)]
         price:u64,
     },
+    WithdrawProceeds{
+        #[allow(dead_code)]
+        lamports:u64,
+        #[allow(dead_code)]
+        destination:Pubkey,
+    },
+    SetSplit{
+        #[allow(dead_code)]
+        recipients:Vec<SplitShare>,
+        #[allow(dead_code)]
+        dust_recipient:Pubkey,
+    },
+    DistributeProceeds,
+    SetReferralBps{
+        #[allow(dead_code)]
+        bps:u16,
+    },
+    Deposit{
+        #[allow(dead_code)]
+        amount:u64,
+    },
+    CloseSale{
+        #[allow(dead_code)]
+        destination:Pubkey,
+    },
+    SetSaleEnd{
+        #[allow(dead_code)]
+        end_ts:i64,
+    },
+    BurnUnsold,
+    TakeMintAuthority{
+        #[allow(dead_code)]
+        supply_cap:u64,
+    },
+    ReleaseMintAuthority{
+        #[allow(dead_code)]
+        new_authority:Pubkey,
+    },
+    SetAdmins{
+        #[allow(dead_code)]
+        signers:Vec<Pubkey>,
+        #[allow(dead_code)]
+        threshold:u8,
+    },
+    SchedulePrice{
+        #[allow(dead_code)]
+        price:u64,
+        #[allow(dead_code)]
+        effective_ts:i64,
+    },
+    CancelPriceChange,
+    SetPriceBounds{
+        #[allow(dead_code)]
+        floor:u64,
+        #[allow(dead_code)]
+        ceiling:u64,
+        #[allow(dead_code)]
+        max_change_bps:u16,
+        /// Length in seconds of the window `max_window_change_bps` applies to; 0 means no window.
+        #[allow(dead_code)]
+        window_secs:i64,
+        #[allow(dead_code)]
+        max_window_change_bps:u16,
+    },
+    Migrate,
+    SetLimitTarget{
+        #[allow(dead_code)]
+        target:LimitTarget,
+    },
+    Distribute{
+        #[allow(dead_code)]
+        payouts:Vec<Payout>,
+    },
+    WithdrawLamports{
+        #[allow(dead_code)]
+        lamports:u64,
+        #[allow(dead_code)]
+        destination:Pubkey,
+    },
+    GrantRole{
+        #[allow(dead_code)]
+        wallet:Pubkey,
+        #[allow(dead_code)]
+        role:Role,
+    },
+    RevokeRole{
+        #[allow(dead_code)]
+        wallet:Pubkey,
+        #[allow(dead_code)]
+        role:Role,
+    },
+    SetPaused{
+        #[allow(dead_code)]
+        paused:bool,
+    },
+    SetVoucherSigner{
+        #[allow(dead_code)]
+        signer:Pubkey,
+    },
+    Block{
+        #[allow(dead_code)]
+        wallet:Pubkey,
+    },
+    Unblock{
+        #[allow(dead_code)]
+        wallet:Pubkey,
+    },
+    SetTokenGate{
+        #[allow(dead_code)]
+        mint:Pubkey,
+        #[allow(dead_code)]
+        min_balance:u64,
+        #[allow(dead_code)]
+        cap_bps:u32,
+    },
+    SetRounds{
+        #[allow(dead_code)]
+        rounds:Vec<SaleRound>,
+    },
+    SetPurchaseLimits{
+        #[allow(dead_code)]
+        min_purchase:u64,
+        #[allow(dead_code)]
+        lot_size:u64,
+    },
+    UnwrapProceeds,
+}
+
+/// What `Buy` does when fewer tokens are available than requested.
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum FillMode{
+    /// Fail with `InsufficientInventory` before charging anything.
+    AllOrNothing,
+    /// Deliver what is available, rounded down to whole lots, and charge only for that.
+    Partial,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for FillMode{
+    fn default() -> Self {
+        FillMode::AllOrNothing
+    }
+}
+
+/// What the buyer pays with.
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum PaymentSource{
+    /// Native lamports, moved into the treasury PDA by a system transfer.
+    Lamports,
+    /// Wrapped SOL from one of the payer's token accounts, moved into the treasury's wSOL ATA.
+    WrappedSol,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for PaymentSource{
+    fn default() -> Self {
+        PaymentSource::Lamports
+    }
+}
+
+/// Failures callers are expected to tell apart, surfaced as `ProgramError::Custom(code)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SellError{
+    ZeroPrice = 1,
+    PriceBelowFloor = 2,
+    PriceAboveCeiling = 3,
+    PriceChangeTooLarge = 4,
+    PriceBoundsLocked = 5,
+    AccountNotMigrated = 6,
+    UnknownAccountVersion = 7,
+    SalePaused = 8,
+    InvalidVoucher = 9,
+    VoucherExpired = 10,
+    VoucherAmountExceeded = 11,
+    VoucherUsed = 12,
+    WalletBlocked = 13,
+    InvalidGateAccount = 14,
+    GateBalanceTooLow = 15,
+    GateCapExceeded = 16,
+    NoActiveRound = 17,
+    RoundAllocationExceeded = 18,
+    RoundWalletCapExceeded = 19,
+    NotAllowlisted = 20,
+    BelowMinimumPurchase = 21,
+    NotALotMultiple = 22,
+    InsufficientInventory = 23,
+    PriceAlreadySet = 24,
+}
+
+impl From<SellError> for ProgramError{
+    fn from(e: SellError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct PriceData{
+    pub price: u64,
+    /// Price `Buy` switches to once `pending_ts` has passed; 0 when no change is scheduled.
+    pub pending_price: u64,
+    pub pending_ts: i64,
+    /// When the current price window opened, and the price it opened at; see `SaleConfig::check_price_window`.
+    pub window_start: i64,
+    pub window_price: u64,
+}
+
+/// Shortest notice, in seconds, a scheduled price change has to give buyers.
+pub const MIN_PRICE_DELAY: i64 = 24 * 60 * 60;
+
+impl PriceData{
+    pub const LEN: usize = 8 + 8 + 8 + 8 + 8;
+
+    /// Promotes the pending price once its time has come. Returns true if the price changed.
+    pub fn apply_pending(&mut self, now: i64) -> bool {
+        if self.pending_price==0 || now<self.pending_ts{
+            return false;
+        }
+        self.price = self.pending_price;
+        self.pending_price = 0;
+        self.pending_ts = 0;
+        true
+    }
+}
+
+/// Proceeds ledger kept in the treasury PDA, which also holds the raised lamports. Wrapped SOL
+/// payments wait in the treasury's wSOL ATA until `UnwrapProceeds`, but count as raised at once.
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct TreasuryData{
+    pub total_raised: u64,
+    pub total_withdrawn: u64,
+}
+
+impl TreasuryData{
+    pub const LEN: usize = 8 + 8;
+}
+
+/// One party's cut of the proceeds, in basis points of what the treasury distributes.
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct SplitShare{
+    pub wallet: Pubkey,
+    pub bps: u16,
+}
+
+/// One airdrop transfer made by `Distribute`.
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct Payout{
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
+
+/// Most payouts one `Distribute` takes; each may create an ATA, which is what the compute budget runs out on.
+pub const MAX_DISTRIBUTE_PAYOUTS: usize = 6;
+
+/// Sale settings kept in the "config" PDA. The body is allocated at `LEN`,
+/// which leaves room for the split list to grow.
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct SaleConfig{
+    pub split: Vec<SplitShare>,
+    pub dust_recipient: Pubkey,
+    pub referral_bps: u16,
+    /// Unix timestamp after which `Buy` is closed and `BurnUnsold` opens; 0 means no end.
+    pub sale_end: i64,
+    /// While set, the vault PDA holds the mint authority and `Buy` mints instead of transferring.
+    pub mint_on_demand: bool,
+    /// Total mint supply `Buy` may never exceed in mint-on-demand mode.
+    pub supply_cap: u64,
+    /// Price bounds, fixed by the first `SetPriceBounds`; a ceiling of 0 means none were set.
+    pub price_floor: u64,
+    pub price_ceiling: u64,
+    /// Largest move a single scheduled price or round may make, in bps of the price it replaces;
+    /// 0 means unlimited. The opening `SetPrice` is held to the bounds only.
+    pub max_price_change_bps: u16,
+    /// Largest move all updates landing within `price_window` seconds may make together, in bps
+    /// of the price the window opened at; a window of 0 means unlimited.
+    pub price_window: i64,
+    pub max_window_change_bps: u16,
+    /// Whose wallet purchase caps and allowlists look at when a purchase is a gift.
+    pub limit_target: LimitTarget,
+    /// While set, `Buy` fails with `SalePaused`.
+    pub paused: bool,
+    /// Key whose `Voucher` every purchase needs; the default key means no vouchers.
+    pub voucher_signer: Pubkey,
+    /// Mint buyers have to hold at least `gate_min_balance` of; the default key means no gate.
+    pub gate_mint: Pubkey,
+    pub gate_min_balance: u64,
+    /// Largest single purchase, in bps of the buyer's gate balance; 0 means uncapped.
+    pub gate_cap_bps: u32,
+    /// Smallest amount `Buy` takes; a purchase of nothing is refused either way.
+    pub min_purchase: u64,
+    /// `Buy` amounts have to be a multiple of this; 0 means any amount.
+    pub lot_size: u64,
+}
+
+/// The side of a purchase that caps and allowlists apply to.
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum LimitTarget{
+    Payer,
+    Recipient,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for LimitTarget{
+    fn default() -> Self {
+        LimitTarget::Payer
+    }
+}
+
+pub const MAX_SPLIT_RECIPIENTS: usize = 8;
+pub const TOTAL_BPS: u16 = 10_000;
+
+impl SaleConfig{
+    pub const LEN: usize = 4 + MAX_SPLIT_RECIPIENTS * (32 + 2) + 32 + 2 + 8 + 1 + 8 + 8 + 8 + 2 + 8 + 2 + 1 + 1 + 32 + 32 + 8 + 4 + 8 + 8;
+
+    /// Checks a purchase amount against `min_purchase` and `lot_size`.
+    pub fn check_amount(&self, amount: u64) -> ProgramResult {
+        if amount==0 || amount<self.min_purchase{
+            msg!("Purchases start at {} tokens", self.min_purchase.max(1));
+            return Err(SellError::BelowMinimumPurchase.into());
+        }
+        if self.lot_size>1 && amount / self.lot_size * self.lot_size!=amount{
+            msg!("Amount has to be a multiple of {}", self.lot_size);
+            return Err(SellError::NotALotMultiple.into());
+        }
+        Ok(())
+    }
+
+    /// The wallet purchase limits are checked against, per `limit_target`.
+    pub fn limited_wallet<'a>(&self, payer: &'a Pubkey, recipient: &'a Pubkey) -> &'a Pubkey {
+        match self.limit_target{
+            LimitTarget::Payer=>payer,
+            LimitTarget::Recipient=>recipient,
+        }
+    }
+
+    /// Checks a price against the floor and ceiling alone. The opening `SetPrice` has nothing to
+    /// move from, so this is all it gets; the change limits bind `SchedulePrice` and `SetRounds`.
+    pub fn check_bounds(&self, price: u64) -> ProgramResult {
+        if price==0{
+            msg!("Price can't be zero");
+            return Err(SellError::ZeroPrice.into());
+        }
+        if price<self.price_floor{
+            msg!("Price {} is below the floor of {}", price, self.price_floor);
+            return Err(SellError::PriceBelowFloor.into());
+        }
+        if self.price_ceiling>0 && price>self.price_ceiling{
+            msg!("Price {} is above the ceiling of {}", price, self.price_ceiling);
+            return Err(SellError::PriceAboveCeiling.into());
+        }
+        Ok(())
+    }
+
+    /// Checks a new price against the guardrails, `current` being the price it replaces (0 if none).
+    pub fn check_price(&self, current: u64, price: u64) -> ProgramResult {
+        self.check_bounds(price)?;
+        if current>0 && self.max_price_change_bps>0{
+            let change = (price as i128 - current as i128).unsigned_abs();
+            if change * TOTAL_BPS as u128 > current as u128 * self.max_price_change_bps as u128{
+                msg!("Price can move at most {} bps per update", self.max_price_change_bps);
+                return Err(SellError::PriceChangeTooLarge.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks a price taking effect at `effective_ts` against the window it lands in, opening
+    /// a new window at the price it replaces once the previous one has run out.
+    pub fn check_price_window(&self, price_data: &mut PriceData, price: u64, effective_ts: i64) -> ProgramResult {
+        if self.price_window==0{
+            return Ok(());
+        }
+        if price_data.window_price==0 || effective_ts>=price_data.window_start.saturating_add(self.price_window){
+            price_data.window_start = effective_ts;
+            price_data.window_price = price_data.price;
+        }
+        let change = (price as i128 - price_data.window_price as i128).unsigned_abs();
+        if change * TOTAL_BPS as u128 > price_data.window_price as u128 * self.max_window_change_bps as u128{
+            msg!("Price can move at most {} bps per {} seconds", self.max_window_change_bps, self.price_window);
+            return Err(SellError::PriceChangeTooLarge.into());
+        }
+        Ok(())
+    }
+}
+
+/// Off-chain approval of one purchase for KYC'd sales. The voucher signer signs `message`,
+/// and the buyer passes that signature to the Ed25519 program in the same transaction as `Buy`.
+/// Each redeemed nonce leaves an empty ["voucher", buyer, nonce] PDA behind, so it can't be replayed.
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct Voucher{
+    pub buyer: Pubkey,
+    pub max_amount: u64,
+    pub expiry: i64,
+    pub nonce: u64,
+}
+
+impl Voucher{
+    pub const LEN: usize = 32 + 8 + 8 + 8;
+
+    /// The signed bytes: the program id, so a voucher only works for this sale, then the voucher.
+    pub fn message(&self, program_id: &Pubkey) -> Vec<u8> {
+        let mut message = program_id.to_bytes().to_vec();
+        message.extend(self.try_to_vec().unwrap());
+        message
+    }
+}
+
+/// Per-referrer totals kept in the ["referrer", referrer] PDA.
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct ReferrerData{
+    pub referrer: Pubkey,
+    pub purchases: u64,
+    pub tokens_sold: u64,
+    pub lamports_earned: u64,
+}
+
+impl ReferrerData{
+    pub const LEN: usize = 32 + 8 + 8 + 8;
+}
+
+/// Blocklist entry kept in the ["blocked", wallet] PDA. `Buy` refuses payers, recipients
+/// and referrers that have one; `Unblock` closes it.
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct BlockedWallet{
+    pub wallet: Pubkey,
+    pub blocked_at: i64,
+}
+
+impl BlockedWallet{
+    pub const LEN: usize = 32 + 8;
+}
+
+/// Canonical bumps of the program's fixed PDAs. `Generate` searches for them once and caches
+/// them in the "state" PDA, so instructions can check addresses with `create_program_address`.
+/// A zero bump means "not cached yet"; a real canonical bump of 0 is practically impossible.
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct Bumps{
+    pub vault: u8,
+    pub treasury: u8,
+    pub price: u8,
+    pub config: u8,
+    pub state: u8,
+    pub admin: u8,
+    /// The temporary wSOL account `UnwrapProceeds` closes into the treasury.
+    pub unwrap: u8,
+}
+
+impl Bumps{
+    pub const LEN: usize = 7;
+
+    /// The expensive search, only meant for initialization and as a fallback.
+    pub fn find(program_id: &Pubkey) -> Self {
+        let find = |seed: &str| Pubkey::find_program_address(&[seed.as_bytes()], program_id).1;
+        Bumps{
+            vault: find("vault"),
+            treasury: find("treasury"),
+            price: find("price"),
+            config: find("config"),
+            state: find("state"),
+            admin: find("admin"),
+            unwrap: find("unwrap"),
+        }
+    }
+}
+
+/// Inventory ledger kept in the "state" PDA.
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct SaleState{
+    pub deposited: u64,
+    pub sold: u64,
+    pub withdrawn: u64,
+    pub burned: u64,
+    pub bumps: Bumps,
+    pub distributed: u64,
+    /// Sale phases in time order. While there are any, `Buy` only works inside one of them.
+    pub rounds: Vec<SaleRound>,
+}
+
+impl SaleState{
+    pub const LEN: usize = 8 + 8 + 8 + 8 + Bumps::LEN + 8 + 4 + MAX_SALE_ROUNDS * SaleRound::LEN;
+
+    /// Index of the round open at `now`, if any.
+    pub fn active_round(&self, now: i64) -> Option<usize> {
+        self.rounds.iter().position(|round| round.start_ts<=now && now<round.end_ts)
+    }
+}
+
+pub const MAX_SALE_ROUNDS: usize = 6;
+
+/// One sale phase, e.g. seed, private or public, with its own price and limits.
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct SaleRound{
+    pub start_ts: i64,
+    pub end_ts: i64,
+    /// Lamports per token, replacing the price account's price during the round.
+    pub price: u64,
+    /// Tokens the round may sell in total.
+    pub allocation: u64,
+    /// Tokens one wallet may buy in the round; 0 means uncapped.
+    pub wallet_cap: u64,
+    /// Merkle root of the wallets allowed in, see `verify_allowlist`; all zero means open to anyone.
+    pub allowlist_root: [u8; 32],
+    /// Tokens sold in the round so far, kept by the program.
+    pub sold: u64,
+}
+
+impl SaleRound{
+    pub const LEN: usize = 8 + 8 + 8 + 8 + 8 + 32 + 8;
+}
+
+/// What one wallet bought in one round, kept in the ["round", [index], wallet] PDA of rounds with a wallet cap.
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct RoundPurchase{
+    pub wallet: Pubkey,
+    pub round: u8,
+    pub bought: u64,
+    /// Start of the round `bought` counts for. `SetRounds` may put a new round at the same index,
+    /// which starts the wallet from zero again.
+    pub start_ts: i64,
+}
+
+impl RoundPurchase{
+    pub const LEN: usize = 32 + 1 + 8 + 8;
+}
+
+/// Allowlist leaves and inner nodes are hashed under different prefixes, so a node can't pass for a leaf.
+pub fn allowlist_leaf(wallet: &Pubkey) -> [u8; 32] {
+    hashv(&[&[0], wallet.as_ref()]).to_bytes()
+}
+
+/// Parent of two allowlist nodes. The pair is sorted first, so proofs don't need to say which side a sibling is on.
+pub fn allowlist_node(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a<=b { (a, b) } else { (b, a) };
+    hashv(&[&[1], left, right]).to_bytes()
+}
+
+pub fn verify_allowlist(root: &[u8; 32], wallet: &Pubkey, proof: &[[u8; 32]]) -> bool {
+    proof.iter().fold(allowlist_leaf(wallet), |node, sibling| allowlist_node(&node, sibling))==*root
+}
+
+/// Signs privileged instructions until `SetAdmins` hands authority to an on-chain signer set.
+pub const BOOTSTRAP_ADMIN: &str = "Ek6Vqf4cCq6zXAp9TwSqeAbQXm8Eo3Y8DV7abbJYntwv";
+pub const MAX_ADMIN_SIGNERS: usize = 10;
+
+pub const MAX_ROLE_GRANTS: usize = 16;
+
+/// Capabilities the admin set can hand to a single key, e.g. letting a pricing bot
+/// move the price without being able to touch the vault.
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum Role{
+    /// SetPrice, SchedulePrice, CancelPriceChange and SetRounds, since rounds set their own prices.
+    Price,
+    /// Withdraw, Distribute, WithdrawProceeds, WithdrawLamports and UnwrapProceeds, along with
+    /// SetSplit and SetReferralBps: anyone may run `DistributeProceeds`, so choosing who the
+    /// proceeds go to is as good as withdrawing them.
+    Withdraw,
+    /// SetPaused.
+    Pause,
+    /// SetSaleEnd, SetPriceBounds, SetLimitTarget, SetVoucherSigner, Block, Unblock,
+    /// SetTokenGate and SetPurchaseLimits.
+    Config,
+}
+
+impl Role{
+    pub fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The roles one wallet holds, as a mask of `Role::bit`.
+#[derive(Clone, Debug, Default, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct RoleGrant{
+    pub wallet: Pubkey,
+    pub roles: u8,
+}
+
+/// M-of-N admin authority kept in the "admin" PDA, along with the roles it has delegated.
+/// The admin set itself holds every role.
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct AdminData{
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub roles: Vec<RoleGrant>,
+}
+
+impl AdminData{
+    pub const LEN: usize = 4 + MAX_ADMIN_SIGNERS * 32 + 1 + 4 + MAX_ROLE_GRANTS * (32 + 1);
+
+    pub fn has_role(&self, wallet: &Pubkey, role: Role) -> bool {
+        self.roles.iter().any(|grant| grant.wallet==*wallet && grant.roles & role.bit()!=0)
+    }
+}
+
+/// Every program-owned data account starts with an 8-byte discriminator and a version byte.
+/// The vault PDA is the exception: it only signs, and carries no data until `CloseSale`.
+pub const ACCOUNT_HEADER_LEN: usize = 8 + 1;
+
+/// All the vault PDA ever holds: `CloseSale` writes it, and `Generate` refuses to set up a sale
+/// whose vault carries it. A vault without it, even one without a state PDA, is still open.
+pub const CLOSED_VAULT_MARKER: [u8; 8] = *b"SALECLSD";
+
+pub fn is_closed_vault(data: &[u8]) -> bool {
+    data.starts_with(&CLOSED_VAULT_MARKER)
+}
+
+/// Layout of a program-owned data account. Layouts are append-only: a new version may only
+/// add fields at the end, and all-zero bytes for them must decode as the old behaviour,
+/// which is what lets `Migrate` upgrade a versioned account by zero-extending it. A version
+/// that grows something in the middle says so in `upgrade_body`.
+pub trait Versioned: BorshSerialize + BorshDeserialize {
+    const DISCRIMINATOR: [u8; 8];
+    const VERSION: u8;
+    /// Largest serialized size of the body, header excluded.
+    const BODY_LEN: usize;
+    /// Size the account is allocated at.
+    const SIZE: usize = ACCOUNT_HEADER_LEN + Self::BODY_LEN;
+
+    /// Reshapes a body written at `version` so that zero-extending it yields the current layout.
+    fn upgrade_body(_version: u8, _body: &mut Vec<u8>) {}
+
+    /// Decodes the headerless layout the account had before versioning; only the price account had one.
+    fn from_unversioned(_data: &[u8]) -> Result<Self, ProgramError> {
+        msg!("Account type has no unversioned layout");
+        Err(SellError::UnknownAccountVersion.into())
+    }
+}
+
+impl Versioned for PriceData{
+    const DISCRIMINATOR: [u8; 8] = *b"PRICEDAT";
+    const VERSION: u8 = 2;
+    const BODY_LEN: usize = PriceData::LEN;
+
+    /// The original `SetPrice` wrote nothing but the 8-byte price.
+    fn from_unversioned(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len()!=8{
+            msg!("Unknown unversioned price layout");
+            return Err(SellError::UnknownAccountVersion.into());
+        }
+        Ok(PriceData{price: u64::try_from_slice(data)?, ..PriceData::default()})
+    }
+}
+
+impl Versioned for TreasuryData{
+    const DISCRIMINATOR: [u8; 8] = *b"TREASURY";
+    const VERSION: u8 = 1;
+    const BODY_LEN: usize = TreasuryData::LEN;
+}
+
+impl Versioned for SaleConfig{
+    const DISCRIMINATOR: [u8; 8] = *b"SALECONF";
+    const VERSION: u8 = 7;
+    const BODY_LEN: usize = SaleConfig::LEN;
+}
+
+impl Versioned for ReferrerData{
+    const DISCRIMINATOR: [u8; 8] = *b"REFERRER";
+    const VERSION: u8 = 1;
+    const BODY_LEN: usize = ReferrerData::LEN;
+}
+
+impl Versioned for SaleState{
+    const DISCRIMINATOR: [u8; 8] = *b"SALESTAT";
+    const VERSION: u8 = 5;
+    const BODY_LEN: usize = SaleState::LEN;
+
+    /// Version 5 added the "unwrap" bump to the cached `Bumps`, which sit mid-body since version 2.
+    fn upgrade_body(version: u8, body: &mut Vec<u8>) {
+        const OLD_BUMPS_END: usize = 8 + 8 + 8 + 8 + 6;
+        if (2..5).contains(&version) && body.len()>=OLD_BUMPS_END{
+            body.insert(OLD_BUMPS_END, 0);
+        }
+    }
+}
+
+impl Versioned for AdminData{
+    const DISCRIMINATOR: [u8; 8] = *b"ADMINSET";
+    const VERSION: u8 = 2;
+    const BODY_LEN: usize = AdminData::LEN;
+}
+
+impl Versioned for BlockedWallet{
+    const DISCRIMINATOR: [u8; 8] = *b"BLOCKLST";
+    const VERSION: u8 = 1;
+    const BODY_LEN: usize = BlockedWallet::LEN;
+}
+
+impl Versioned for RoundPurchase{
+    const DISCRIMINATOR: [u8; 8] = *b"ROUNDBUY";
+    const VERSION: u8 = 2;
+    const BODY_LEN: usize = RoundPurchase::LEN;
+}
+
+/// True if `data` already holds the current layout of `T`, i.e. `Migrate` has nothing to do.
+pub fn is_current<T: Versioned>(data: &[u8]) -> bool {
+    data.len()>=T::SIZE && data[..8]==T::DISCRIMINATOR && data[8]==T::VERSION
+}
+
+/// Decodes a program-owned account's data, header included. Shared with the client.
+pub fn unpack_account<T: Versioned>(data: &[u8]) -> Result<T, ProgramError> {
+    if data.len()<ACCOUNT_HEADER_LEN || data[..8]!=T::DISCRIMINATOR{
+        msg!("Account predates versioning, run Migrate first");
+        return Err(SellError::AccountNotMigrated.into());
+    }
+    if data[8]>T::VERSION{
+        msg!("Account version {} is newer than this program", data[8]);
+        return Err(SellError::UnknownAccountVersion.into());
+    }
+    if !is_current::<T>(data){
+        msg!("Account is at version {}, run Migrate first", data[8]);
+        return Err(SellError::AccountNotMigrated.into());
+    }
+    try_from_slice_unchecked(&data[ACCOUNT_HEADER_LEN..]).map_err(|e| e.into())
 }
 
-#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
-struct PriceData{
-    price: u64,
-}
+/// Bumped whenever a `SellEvent` variant changes shape, so indexers can tell layouts apart.
+pub const EVENT_VERSION: u8 = 1;
+/// Marks program log lines carrying a base64 encoded `EventRecord`.
+pub const EVENT_LOG_PREFIX: &str = "EVENT:";
+
+/// Everything a state-changing instruction reports. New variants go at the end.
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum SellEvent{
+    Generated{
+        #[allow(dead_code)]
+        admin: Pubkey,
+        #[allow(dead_code)]
+        created: u8,
+    },
+    Bought{
+        #[allow(dead_code)]
+        buyer: Pubkey,
+        #[allow(dead_code)]
+        amount: u64,
+        #[allow(dead_code)]
+        lamports: u64,
+        #[allow(dead_code)]
+        price: u64,
+        #[allow(dead_code)]
+        referrer: Option<Pubkey>,
+        #[allow(dead_code)]
+        commission: u64,
+        /// Wallet the tokens went to; the buyer unless the purchase was a gift.
+        #[allow(dead_code)]
+        recipient: Pubkey,
+        #[allow(dead_code)]
+        payment: PaymentSource,
+    },
+    Withdrawn{
+        #[allow(dead_code)]
+        destination: Pubkey,
+        #[allow(dead_code)]
+        amount: u64,
+    },
+    PriceSet{
+        #[allow(dead_code)]
+        price: u64,
+    },
+    ProceedsWithdrawn{
+        #[allow(dead_code)]
+        destination: Pubkey,
+        #[allow(dead_code)]
+        lamports: u64,
+    },
+    SplitSet{
+        #[allow(dead_code)]
+        recipients: Vec<SplitShare>,
+        #[allow(dead_code)]
+        dust_recipient: Pubkey,
+    },
+    ProceedsDistributed{
+        #[allow(dead_code)]
+        lamports: u64,
+        #[allow(dead_code)]
+        dust: u64,
+    },
+    ReferralBpsSet{
+        #[allow(dead_code)]
+        bps: u16,
+    },
+    Deposited{
+        #[allow(dead_code)]
+        amount: u64,
+    },
+    SaleClosed{
+        #[allow(dead_code)]
+        destination: Pubkey,
+        #[allow(dead_code)]
+        leftover: u64,
+    },
+    SaleEndSet{
+        #[allow(dead_code)]
+        end_ts: i64,
+    },
+    UnsoldBurned{
+        #[allow(dead_code)]
+        amount: u64,
+        #[allow(dead_code)]
+        total_burned: u64,
+    },
+    MintAuthorityTaken{
+        #[allow(dead_code)]
+        supply_cap: u64,
+    },
+    MintAuthorityReleased{
+        #[allow(dead_code)]
+        new_authority: Pubkey,
+    },
+    AdminsSet{
+        #[allow(dead_code)]
+        signers: Vec<Pubkey>,
+        #[allow(dead_code)]
+        threshold: u8,
+    },
+    PriceScheduled{
+        #[allow(dead_code)]
+        price: u64,
+        #[allow(dead_code)]
+        effective_ts: i64,
+    },
+    PriceChangeCancelled{
+        #[allow(dead_code)]
+        price: u64,
+    },
+    PriceBoundsSet{
+        #[allow(dead_code)]
+        floor: u64,
+        #[allow(dead_code)]
+        ceiling: u64,
+        #[allow(dead_code)]
+        max_change_bps: u16,
+    },
+    AccountMigrated{
+        #[allow(dead_code)]
+        account: Pubkey,
+        #[allow(dead_code)]
+        version: u8,
+    },
+    LimitTargetSet{
+        #[allow(dead_code)]
+        target: LimitTarget,
+    },
+    Distributed{
+        #[allow(dead_code)]
+        recipient: Pubkey,
+        #[allow(dead_code)]
+        amount: u64,
+    },
+    VaultLamportsWithdrawn{
+        #[allow(dead_code)]
+        destination: Pubkey,
+        #[allow(dead_code)]
+        lamports: u64,
+    },
+    RoleGranted{
+        #[allow(dead_code)]
+        wallet: Pubkey,
+        #[allow(dead_code)]
+        role: Role,
+    },
+    RoleRevoked{
+        #[allow(dead_code)]
+        wallet: Pubkey,
+        #[allow(dead_code)]
+        role: Role,
+    },
+    PausedSet{
+        #[allow(dead_code)]
+        paused: bool,
+    },
+    VoucherSignerSet{
+        #[allow(dead_code)]
+        signer: Pubkey,
+    },
+    VoucherRedeemed{
+        #[allow(dead_code)]
+        buyer: Pubkey,
+        #[allow(dead_code)]
+        nonce: u64,
+        #[allow(dead_code)]
+        amount: u64,
+    },
+    WalletBlocked{
+        #[allow(dead_code)]
+        wallet: Pubkey,
+    },
+    WalletUnblocked{
+        #[allow(dead_code)]
+        wallet: Pubkey,
+    },
+    TokenGateSet{
+        #[allow(dead_code)]
+        mint: Pubkey,
+        #[allow(dead_code)]
+        min_balance: u64,
+        #[allow(dead_code)]
+        cap_bps: u32,
+    },
+    RoundsSet{
+        #[allow(dead_code)]
+        rounds: Vec<SaleRound>,
+    },
+    PurchaseLimitsSet{
+        #[allow(dead_code)]
+        min_purchase: u64,
+        #[allow(dead_code)]
+        lot_size: u64,
+    },
+    ProceedsUnwrapped{
+        #[allow(dead_code)]
+        lamports: u64,
+    },
+    PriceWindowSet{
+        #[allow(dead_code)]
+        window_secs: i64,
+        #[allow(dead_code)]
+        max_change_bps: u16,
+    },
+}
+
+/// What actually gets logged: the event plus the layout version and the slot it happened in.
+#[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct EventRecord{
+    pub version: u8,
+    pub slot: u64,
+    pub event: SellEvent,
+}
+
+fn emit(event: SellEvent) -> ProgramResult {
+    let record = EventRecord{version: EVENT_VERSION, slot: Clock::get()?.slot, event};
+    msg!("{}{}", EVENT_LOG_PREFIX, base64::encode(record.try_to_vec()?));
+    Ok(())
+}
+
+/// Decodes a program log line written by `emit`, with or without the "Program log: " prefix.
+/// Returns `None` for any other line and for events of another version.
+pub fn decode_event(log: &str) -> Option<EventRecord> {
+    let line = log.strip_prefix("Program log: ").unwrap_or(log);
+    let encoded = line.strip_prefix(EVENT_LOG_PREFIX)?;
+    let bytes = base64::decode(encoded).ok()?;
+    let (&version, rest) = bytes.split_first()?;
+    if version!=EVENT_VERSION || rest.len()<8{
+        return None;
+    }
+    let (slot, body) = rest.split_at(8);
+    Some(EventRecord{version, slot: u64::try_from_slice(slot).ok()?, event: SellEvent::try_from_slice(body).ok()?})
+}
+
+/// The treasury PDA's associated account for the native mint, where wrapped SOL payments land.
+pub fn treasury_wsol_address(treasury: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address(treasury, &spl_token::native_mint::id())
+}
+
+/// Moves `lamports` of wrapped SOL between two native-mint token accounts, signed by `authority`.
+fn transfer_wsol<'a>(
+    token_info: &AccountInfo<'a>,
+    source: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    lamports: u64,
+) -> ProgramResult {
+    invoke(
+        &spl_token::instruction::transfer(
+            token_info.key,
+            source.key,
+            destination.key,
+            authority.key,
+            &[],
+            lamports,
+        )?,
+        &[
+            source.clone(),
+            destination.clone(),
+            authority.clone(),
+            token_info.clone(),
+        ],
+    )
+}
+
+/// Moves all lamports of a program-owned account to `destination` and wipes its data.
+fn close_program_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    **account.try_borrow_mut_lamports()? -= lamports;
+    **destination.try_borrow_mut_lamports()? += lamports;
+    account.try_borrow_mut_data()?.fill(0);
+    Ok(())
+}
+
+fn read_account<T: Versioned>(account: &AccountInfo) -> Result<T, ProgramError> {
+    unpack_account(&account.data.borrow())
+}
+
+/// Writes the header and `value`, zeroing whatever is left so a shrunk `Vec` leaves no stale bytes.
+fn write_account<T: Versioned>(account: &AccountInfo, value: &T) -> ProgramResult {
+    let body = value.try_to_vec()?;
+    let mut data = account.try_borrow_mut_data()?;
+    if data.len()<T::SIZE || body.len()>T::BODY_LEN{
+        msg!("Account is too small for its layout, run Migrate first");
+        return Err(SellError::AccountNotMigrated.into());
+    }
+    data[..8].copy_from_slice(&T::DISCRIMINATOR);
+    data[8] = T::VERSION;
+    let (filled, tail) = data[ACCOUNT_HEADER_LEN..].split_at_mut(body.len());
+    filled.copy_from_slice(&body);
+    tail.fill(0);
+    Ok(())
+}
+
+/// Rewrites an account in the current layout of `T`, growing it and topping up rent as needed.
+/// Headerless accounts go through `Versioned::from_unversioned`. Returns false if it was already current.
+fn migrate_account<'a, T: Versioned>(
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    rent: &Rent,
+) -> Result<bool, ProgramError> {
+    let value: T = {
+        let data = account.data.borrow();
+        if is_current::<T>(&data){
+            return Ok(false);
+        }
+        if data.len()<ACCOUNT_HEADER_LEN || data[..8]!=T::DISCRIMINATOR{
+            T::from_unversioned(&data)?
+        } else {
+            if data[8]>T::VERSION{
+                msg!("Account version {} is newer than this program", data[8]);
+                return Err(SellError::UnknownAccountVersion.into());
+            }
+            // `write_account` zeroes the tail and layouts only grow at the end,
+            // so zero-extending a versioned body yields the new one.
+            let mut body = data[ACCOUNT_HEADER_LEN..].to_vec();
+            T::upgrade_body(data[8], &mut body);
+            if body.len()<T::BODY_LEN{
+                body.resize(T::BODY_LEN, 0);
+            }
+            try_from_slice_unchecked(&body)?
+        }
+    };
+
+    let required_lamports = rent.minimum_balance(T::SIZE).saturating_sub(account.lamports());
+    if required_lamports>0{
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, required_lamports),
+            &[
+                payer.clone(),
+                account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+    account.realloc(T::SIZE, false)?;
+    write_account(account, &value)?;
+    Ok(true)
+}
+
+/// Writes `CLOSED_VAULT_MARKER` into the vault PDA, topping up its rent for the extra bytes.
+fn mark_vault_closed<'a>(
+    payer: &AccountInfo<'a>,
+    vault_info: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    let required_lamports = Rent::get()?.minimum_balance(CLOSED_VAULT_MARKER.len()).saturating_sub(vault_info.lamports());
+    if required_lamports>0{
+        invoke(
+            &system_instruction::transfer(payer.key, vault_info.key, required_lamports),
+            &[
+                payer.clone(),
+                vault_info.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+    vault_info.realloc(CLOSED_VAULT_MARKER.len(), false)?;
+    vault_info.try_borrow_mut_data()?.copy_from_slice(&CLOSED_VAULT_MARKER);
+    Ok(())
+}
+
+/// Fails unless `account` is the PDA of the single `seed` at the cached `bump`.
+fn check_pda(program_id: &Pubkey, account: &AccountInfo, seed: &str, bump: u8) -> ProgramResult {
+    match Pubkey::create_program_address(&[seed.as_bytes(), &[bump]], program_id){
+        Ok(address) if address==*account.key=>Ok(()),
+        _=>{
+            msg!("Wrong {} account", seed);
+            Err(ProgramError::IncorrectProgramId)
+        }
+    }
+}
+
+/// Reads the bumps cached in the "state" PDA. Before `Generate`, after `CloseSale` or while the
+/// state predates the cache it falls back to searching for them.
+fn load_bumps(program_id: &Pubkey, state_info: &AccountInfo) -> Result<Bumps, ProgramError> {
+    if state_info.owner==program_id && is_current::<SaleState>(&state_info.data.borrow()){
+        let bumps = read_account::<SaleState>(state_info)?.bumps;
+        if bumps.state!=0{
+            check_pda(program_id, state_info, "state", bumps.state)?;
+            return Ok(bumps);
+        }
+    }
+    Ok(Bumps::find(program_id))
+}
+
+/// Reads the "config" PDA, falling back to defaults while it hasn't been created yet.
+fn load_config(program_id: &Pubkey, config_info: &AccountInfo, bumps: &Bumps) -> Result<SaleConfig, ProgramError> {
+    check_pda(program_id, config_info, "config", bumps.config)?;
+    if config_info.owner!=program_id{
+        return Ok(SaleConfig::default());
+    }
+    read_account(config_info)
+}
+
+/// Like `load_config`, for instructions that change the config: creates the account on first use.
+fn load_config_mut<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    config_info: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    bumps: &Bumps,
+) -> Result<SaleConfig, ProgramError> {
+    let config = load_config(program_id, config_info, bumps)?;
+    if config_info.owner!=program_id{
+        create_pda_account(
+            payer,
+            config_info,
+            system_program,
+            &Rent::get()?,
+            SaleConfig::SIZE,
+            program_id,
+            &[b"config", &[bumps.config]],
+        )?;
+    }
+    Ok(config)
+}
+
+/// Reads the "state" PDA, which is created by `Generate`.
+fn load_state(program_id: &Pubkey, state_info: &AccountInfo, bumps: &Bumps) -> Result<SaleState, ProgramError> {
+    check_pda(program_id, state_info, "state", bumps.state)?;
+    if state_info.owner!=program_id{
+        msg!("Sale state isn't initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+    read_account(state_info)
+}
+
+/// Reads the "admin" PDA, falling back to `BOOTSTRAP_ADMIN` as a 1-of-1 set while it hasn't been created yet.
+fn load_admins(program_id: &Pubkey, admin_info: &AccountInfo, bumps: &Bumps) -> Result<AdminData, ProgramError> {
+    check_pda(program_id, admin_info, "admin", bumps.admin)?;
+    if admin_info.owner!=program_id{
+        let admin = BOOTSTRAP_ADMIN.parse::<Pubkey>().unwrap();
+        return Ok(AdminData{signers: vec![admin], threshold: 1, roles: Vec::new()});
+    }
+    read_account(admin_info)
+}
+
+/// Fails unless at least `threshold` distinct admin signers signed the transaction.
+/// Signers may sit anywhere in `accounts`; clients append co-signers after the admin PDA.
+fn check_admin(program_id: &Pubkey, admin_info: &AccountInfo, accounts: &[AccountInfo], bumps: &Bumps) -> ProgramResult {
+    let admins = load_admins(program_id, admin_info, bumps)?;
+    check_approvals(&admins, accounts)
+}
+
+/// Passes if any signer was granted `role`, and otherwise asks for the admin threshold.
+fn check_role(program_id: &Pubkey, admin_info: &AccountInfo, accounts: &[AccountInfo], bumps: &Bumps, role: Role) -> ProgramResult {
+    let admins = load_admins(program_id, admin_info, bumps)?;
+    if accounts.iter().any(|account| account.is_signer && admins.has_role(account.key, role)){
+        return Ok(());
+    }
+    check_approvals(&admins, accounts)
+}
+
+fn check_approvals(admins: &AdminData, accounts: &[AccountInfo]) -> ProgramResult {
+    let approvals = admins.signers.iter()
+        .filter(|signer| accounts.iter().any(|account| account.is_signer && account.key==*signer))
+        .count();
+    if approvals<admins.threshold as usize{
+        msg!("Unauthorized access: {} of {} admin signatures", approvals, admins.threshold);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+/// Finds the voucher an Ed25519 program instruction earlier in this transaction verified for `signer`.
+/// Key, signature and message have to sit in that instruction's own data; offsets into other
+/// instructions could make the precompile check bytes this program never looks at.
+fn verified_voucher(program_id: &Pubkey, instructions_info: &AccountInfo, signer: &Pubkey) -> Result<Voucher, ProgramError> {
+    if *instructions_info.key!=sysvar::instructions::id(){
+        msg!("Wrong instructions sysvar");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let current = sysvar::instructions::load_current_index_checked(instructions_info)?;
+    for index in 0..current{
+        let instruction = sysvar::instructions::load_instruction_at_checked(index as usize, instructions_info)?;
+        if instruction.program_id!=ed25519_program::id(){
+            continue;
+        }
+        let data = &instruction.data;
+        let count = data.first().copied().unwrap_or(0) as usize;
+        for offsets in data[2.min(data.len())..].chunks_exact(14).take(count){
+            let read = |at: usize| u16::from_le_bytes([offsets[at], offsets[at + 1]]) as usize;
+            if read(2)!=u16::MAX as usize || read(6)!=u16::MAX as usize || read(12)!=u16::MAX as usize{
+                continue;
+            }
+            let key = data.get(read(4)..read(4) + 32);
+            let message = data.get(read(8)..read(8) + read(10));
+            if let (Some(key), Some(message)) = (key, message){
+                if key==signer.as_ref() && message.len()==32 + Voucher::LEN && message[..32]==program_id.to_bytes(){
+                    return Voucher::try_from_slice(&message[32..]).map_err(|_| SellError::InvalidVoucher.into());
+                }
+            }
+        }
+    }
+
+    msg!("No voucher signed by {:?} in this transaction", signer);
+    Err(SellError::InvalidVoucher.into())
+}
+
+/// Checks `gate_info` is a token account of the gate mint owned by `buyer`, holding enough to buy `amount`.
+fn check_token_gate(config: &SaleConfig, buyer: &Pubkey, gate_info: &AccountInfo, amount: u64) -> ProgramResult {
+    if *gate_info.owner!=spl_token::id(){
+        msg!("Gate account isn't a token account");
+        return Err(SellError::InvalidGateAccount.into());
+    }
+    let gate_account = spl_token::state::Account::unpack(&gate_info.data.borrow())?;
+    if gate_account.mint!=config.gate_mint || gate_account.owner!=*buyer{
+        msg!("Gate account has to hold {:?} for {:?}", config.gate_mint, buyer);
+        return Err(SellError::InvalidGateAccount.into());
+    }
+    if gate_account.amount<config.gate_min_balance{
+        msg!("Holding {} gate tokens, {} needed", gate_account.amount, config.gate_min_balance);
+        return Err(SellError::GateBalanceTooLow.into());
+    }
+    if config.gate_cap_bps>0{
+        let cap = (gate_account.amount as u128 * config.gate_cap_bps as u128 / TOTAL_BPS as u128).min(u64::MAX as u128) as u64;
+        if amount>cap{
+            msg!("Gate balance allows at most {} tokens per purchase", cap);
+            return Err(SellError::GateCapExceeded.into());
+        }
+    }
+    Ok(())
+}
+
+/// Fails with `WalletBlocked` if `wallet` is on the blocklist, `entry_info` being its ["blocked", wallet]
+/// PDA at the canonical `bump` the caller passed in.
+fn check_not_blocked(program_id: &Pubkey, wallet: &Pubkey, entry_info: &AccountInfo, bump: u8) -> ProgramResult {
+    if entry_info.owner==program_id{
+        // Entries are only ever written at ["blocked", wallet], so the stored wallet proves the address.
+        if read_account::<BlockedWallet>(entry_info)?.wallet==*wallet{
+            msg!("{:?} is blocked", wallet);
+            return Err(SellError::WalletBlocked.into());
+        }
+    } else {
+        let entry_address = |bump: u8| Pubkey::create_program_address(&["blocked".as_bytes(), wallet.as_ref(), &[bump]], program_id);
+        // `Block` writes entries at the canonical bump only. A lower bump can land off the curve too,
+        // on an address that never holds an entry, so every higher bump has to be on it.
+        if matches!(entry_address(bump), Ok(address) if address==*entry_info.key)
+            && (bump..=u8::MAX).skip(1).all(|higher| entry_address(higher).is_err()){
+            return Ok(());
+        }
+    }
+    msg!("Wrong blocklist account for {:?}", wallet);
+    Err(ProgramError::IncorrectProgramId)
+}
+
+/// Creates `wallet`'s associated token account for the sale mint at `payer`'s expense, unless it already exists.
+#[allow(clippy::too_many_arguments)]
+fn create_token_account_if_missing<'a>(
+    payer: &AccountInfo<'a>,
+    wallet: &AccountInfo<'a>,
+    token_account: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    token_info: &AccountInfo<'a>,
+    rent_info: &AccountInfo<'a>,
+    assoc_acccount_info: &AccountInfo<'a>,
+) -> ProgramResult {
+    if token_account.owner==token_info.key{
+        return Ok(());
+    }
+    invoke(
+        &spl_associated_token_account::instruction::create_associated_token_account(
+            payer.key,
+            wallet.key,
+            mint_info.key,
+        ),
+        &[
+            payer.clone(),
+            token_account.clone(),
+            wallet.clone(),
+            mint_info.clone(),
+            system_program.clone(),
+            token_info.clone(),
+            rent_info.clone(),
+            assoc_acccount_info.clone(),
+        ],
+    )
+}
+
+/// Funds, allocates and assigns a PDA to this program, `seeds` being the PDA's signer seeds.
+fn create_pda_account<'a>(
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    rent: &Rent,
+    size: usize,
+    program_id: &Pubkey,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let required_lamports = rent
+        .minimum_balance(size)
+        .max(1)
+        .saturating_sub(account.lamports());
+
+    if required_lamports > 0{
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, required_lamports),
+            &[
+                payer.clone(),
+                account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, size as u64),
+        &[
+            account.clone(),
+            system_program.clone(),
+        ],
+        &[seeds],
+    )?;
+
+    invoke_signed(
+        &system_instruction::assign(account.key, program_id),
+        &[
+            account.clone(),
+            system_program.clone(),
+        ],
+        &[seeds],
+    )
+}
+
+// Program entrypoint's implementation
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    msg!("author: Rusty 0ne <4fun.and.job.offers@gmail.com>");
+
+    let accounts_iter = &mut accounts.iter();
+    let instruction: SellInstruction = try_from_slice_unchecked(instruction_data).unwrap();
+
+    let mint = "5CZJ7e4uUWCogW2z7rvcE2yur6yE7Z7kcGYrFqLLXSL9".parse::<Pubkey>().unwrap();
+
+    match instruction{
+        SellInstruction::SetPrice{price}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let price_account_info = next_account_info(accounts_iter)?;
+            let sys_info = next_account_info(accounts_iter)?;
+            let rent_info = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let rent = &Rent::from_account_info(rent_info)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+            
+            let price_data = PriceData{price, ..PriceData::default()};
+            
+            check_role(program_id, admin_info, accounts, &bumps, Role::Price)?;
+            check_pda(program_id, price_account_info, "price", bumps.price)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let current = if price_account_info.owner==program_id{
+                read_account::<PriceData>(price_account_info)?.price
+            } else {
+                0
+            };
+            if current>0{
+                // Only the opening price takes effect immediately, later changes go through SchedulePrice.
+                msg!("Price is already set, use SchedulePrice to change it");
+                return Err(SellError::PriceAlreadySet.into());
+            }
+            load_config(program_id, config_info, &bumps)?.check_bounds(price)?;
+
+            if price_account_info.owner != program_id{
+                create_pda_account(
+                    payer,
+                    price_account_info,
+                    sys_info,
+                    rent,
+                    PriceData::SIZE,
+                    program_id,
+                    &[b"price", &[bumps.price]],
+                )?;
+            }
+
+            write_account(price_account_info, &price_data)?;
+            emit(SellEvent::PriceSet{price})?;
+        }
+        SellInstruction::SchedulePrice{price, effective_ts}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let price_account_info = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Price)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, price_account_info, "price", bumps.price)?;
+            if price_account_info.owner!=program_id{
+                msg!("Wrong price account");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let now = Clock::get()?.unix_timestamp;
+            if effective_ts<now.saturating_add(MIN_PRICE_DELAY){
+                msg!("Price changes need at least {} seconds of notice", MIN_PRICE_DELAY);
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let mut price_data = read_account::<PriceData>(price_account_info)?;
+            if price_data.price==0{
+                msg!("Set the opening price with SetPrice first");
+                return Err(ProgramError::InvalidArgument);
+            }
+            if price_data.apply_pending(now){
+                emit(SellEvent::PriceSet{price: price_data.price})?;
+            }
+            let config = load_config(program_id, config_info, &bumps)?;
+            config.check_price(price_data.price, price)?;
+            config.check_price_window(&mut price_data, price, effective_ts)?;
+            price_data.pending_price = price;
+            price_data.pending_ts = effective_ts;
+            write_account(price_account_info, &price_data)?;
+            emit(SellEvent::PriceScheduled{price, effective_ts})?;
+        }
+        SellInstruction::CancelPriceChange=>{
+            let payer = next_account_info(accounts_iter)?;
+            let price_account_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Price)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, price_account_info, "price", bumps.price)?;
+            if price_account_info.owner!=program_id{
+                msg!("Wrong price account");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let mut price_data = read_account::<PriceData>(price_account_info)?;
+            if price_data.apply_pending(Clock::get()?.unix_timestamp){
+                msg!("Pending price already took effect");
+                return Err(ProgramError::InvalidArgument);
+            }
+            if price_data.pending_price==0{
+                msg!("No price change is pending");
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let cancelled = price_data.pending_price;
+            price_data.pending_price = 0;
+            price_data.pending_ts = 0;
+            write_account(price_account_info, &price_data)?;
+            emit(SellEvent::PriceChangeCancelled{price: cancelled})?;
+        }
+        SellInstruction::Withdraw{amount, destination}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let vault_info = next_account_info(accounts_iter)?;
+
+            let vault_mint_info = next_account_info(accounts_iter)?;
+            let mint_info = next_account_info(accounts_iter)?;
+            let token_info = next_account_info(accounts_iter)?;
+            let rent_info = next_account_info(accounts_iter)?;
+            let assoc_acccount_info = next_account_info(accounts_iter)?;
+
+            let destination_info = next_account_info(accounts_iter)?;
+            let destination_mint_holder_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Withdraw)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *mint_info.key!=mint{
+                msg!("Wrong treasury");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *destination_info.key!=destination{
+                msg!("Wrong destination");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let destination_mint_holder = spl_associated_token_account::get_associated_token_address(&destination, &mint);
+            if *destination_mint_holder_info.key!=destination_mint_holder{
+                msg!("Wrong destination_mint_holder");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            create_token_account_if_missing(
+                payer,
+                destination_info,
+                destination_mint_holder_info,
+                mint_info,
+                system_program,
+                token_info,
+                rent_info,
+                assoc_acccount_info,
+            )?;
+
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    token_info.key,
+                    vault_mint_info.key,
+                    destination_mint_holder_info.key,
+                    vault_info.key,
+                    &[],
+                    amount,
+                )?,
+                &[
+                    vault_mint_info.clone(),
+                    destination_mint_holder_info.clone(),
+                    vault_info.clone(), 
+                    token_info.clone()
+                ],
+                &[&[b"vault", &[bumps.vault]]],
+            )?;
+
+            let mut state = load_state(program_id, state_info, &bumps)?;
+            state.withdrawn = state.withdrawn.saturating_add(amount);
+            write_account(state_info, &state)?;
+            emit(SellEvent::Withdrawn{destination, amount})?;
+        },
+
+        SellInstruction::Distribute{payouts}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let vault_info = next_account_info(accounts_iter)?;
+
+            let vault_mint_info = next_account_info(accounts_iter)?;
+            let mint_info = next_account_info(accounts_iter)?;
+            let token_info = next_account_info(accounts_iter)?;
+            let rent_info = next_account_info(accounts_iter)?;
+            let assoc_acccount_info = next_account_info(accounts_iter)?;
+
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Withdraw)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *mint_info.key!=mint{
+                msg!("Wrong mint");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, vault_info, "vault", bumps.vault)?;
+
+            if payouts.is_empty() || payouts.len()>MAX_DISTRIBUTE_PAYOUTS{
+                msg!("Distribute takes 1 to {} payouts", MAX_DISTRIBUTE_PAYOUTS);
+                return Err(ProgramError::InvalidInstructionData);
+            }
+
+            let mut state = load_state(program_id, state_info, &bumps)?;
+
+            // Each payout is followed by its recipient wallet and that wallet's ATA.
+            for payout in payouts.iter(){
+                let recipient_info = next_account_info(accounts_iter)?;
+                let recipient_mint_holder_info = next_account_info(accounts_iter)?;
+
+                if payout.amount==0{
+                    msg!("Zero payout to {:?}", payout.recipient);
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+
+                if *recipient_info.key!=payout.recipient{
+                    msg!("Wrong recipient {:?}", recipient_info.key);
+                    return Err(ProgramError::InvalidArgument);
+                }
+
+                let recipient_mint_holder = spl_associated_token_account::get_associated_token_address(recipient_info.key, &mint);
+                if *recipient_mint_holder_info.key!=recipient_mint_holder{
+                    msg!("Wrong recipient_mint_holder");
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+
+                create_token_account_if_missing(
+                    payer,
+                    recipient_info,
+                    recipient_mint_holder_info,
+                    mint_info,
+                    system_program,
+                    token_info,
+                    rent_info,
+                    assoc_acccount_info,
+                )?;
+
+                invoke_signed(
+                    &spl_token::instruction::transfer(
+                        token_info.key,
+                        vault_mint_info.key,
+                        recipient_mint_holder_info.key,
+                        vault_info.key,
+                        &[],
+                        payout.amount,
+                    )?,
+                    &[
+                        vault_mint_info.clone(),
+                        recipient_mint_holder_info.clone(),
+                        vault_info.clone(),
+                        token_info.clone()
+                    ],
+                    &[&[b"vault", &[bumps.vault]]],
+                )?;
+
+                state.distributed = state.distributed.saturating_add(payout.amount);
+                emit(SellEvent::Distributed{recipient: payout.recipient, amount: payout.amount})?;
+            }
+
+            write_account(state_info, &state)?;
+        },
+
+        SellInstruction::Deposit{amount}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let vault_info = next_account_info(accounts_iter)?;
+
+            let vault_mint_info = next_account_info(accounts_iter)?;
+            let mint_info = next_account_info(accounts_iter)?;
+            let token_info = next_account_info(accounts_iter)?;
+            let rent_info = next_account_info(accounts_iter)?;
+            let assoc_acccount_info = next_account_info(accounts_iter)?;
+
+            let payer_mint_holder_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+            let vault = *vault_info.key;
+
+            check_admin(program_id, admin_info, accounts, &bumps)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *mint_info.key!=mint{
+                msg!("Wrong mint");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, vault_info, "vault", bumps.vault)?;
+            if vault_info.owner!=program_id{
+                msg!("Vault isn't generated");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let vault_mint_holder = spl_associated_token_account::get_associated_token_address(&vault, &mint);
+            if *vault_mint_info.key!=vault_mint_holder{
+                msg!("Wrong vault_mint_holder");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let payer_mint_holder = spl_associated_token_account::get_associated_token_address(payer.key, &mint);
+            if *payer_mint_holder_info.key!=payer_mint_holder{
+                msg!("Wrong payer_mint_holder");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            create_token_account_if_missing(
+                payer,
+                vault_info,
+                vault_mint_info,
+                mint_info,
+                system_program,
+                token_info,
+                rent_info,
+                assoc_acccount_info,
+            )?;
+
+            // The state PDA is created by `Generate`, also for vaults that predate it; once
+            // `CloseSale` removed it, deposits are refused.
+            let mut state = load_state(program_id, state_info, &bumps)?;
+
+            if amount>0{
+                invoke(
+                    &spl_token::instruction::transfer(
+                        token_info.key,
+                        payer_mint_holder_info.key,
+                        vault_mint_info.key,
+                        payer.key,
+                        &[],
+                        amount,
+                    )?,
+                    &[
+                        payer_mint_holder_info.clone(),
+                        vault_mint_info.clone(),
+                        payer.clone(),
+                        token_info.clone()
+                    ],
+                )?;
+            }
+
+            state.deposited = state.deposited.saturating_add(amount);
+            write_account(state_info, &state)?;
+            emit(SellEvent::Deposited{amount})?;
+        },
+
+        SellInstruction::CloseSale{destination}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let vault_info = next_account_info(accounts_iter)?;
+
+            let vault_mint_info = next_account_info(accounts_iter)?;
+            let mint_info = next_account_info(accounts_iter)?;
+            let token_info = next_account_info(accounts_iter)?;
+            let rent_info = next_account_info(accounts_iter)?;
+            let assoc_acccount_info = next_account_info(accounts_iter)?;
+
+            let destination_info = next_account_info(accounts_iter)?;
+            let destination_mint_holder_info = next_account_info(accounts_iter)?;
+            let price_account_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+            let vault = *vault_info.key;
+
+            check_admin(program_id, admin_info, accounts, &bumps)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *mint_info.key!=mint{
+                msg!("Wrong mint");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, vault_info, "vault", bumps.vault)?;
+
+            if *vault_mint_info.key!=spl_associated_token_account::get_associated_token_address(&vault, &mint){
+                msg!("Wrong vault_mint_holder");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *destination_info.key!=destination{
+                msg!("Wrong destination");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *destination_mint_holder_info.key!=spl_associated_token_account::get_associated_token_address(&destination, &mint){
+                msg!("Wrong destination_mint_holder");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, price_account_info, "price", bumps.price)?;
+            if price_account_info.owner!=program_id{
+                msg!("Wrong price account");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let state = load_state(program_id, state_info, &bumps)?;
+
+            let leftover = if vault_mint_info.owner==token_info.key{
+                spl_token::state::Account::unpack(&vault_mint_info.data.borrow())?.amount
+            } else {
+                0
+            };
+
+            if vault_mint_info.owner==token_info.key{
+                if leftover>0{
+                    create_token_account_if_missing(
+                        payer,
+                        destination_info,
+                        destination_mint_holder_info,
+                        mint_info,
+                        system_program,
+                        token_info,
+                        rent_info,
+                        assoc_acccount_info,
+                    )?;
+
+                    invoke_signed(
+                        &spl_token::instruction::transfer(
+                            token_info.key,
+                            vault_mint_info.key,
+                            destination_mint_holder_info.key,
+                            vault_info.key,
+                            &[],
+                            leftover,
+                        )?,
+                        &[
+                            vault_mint_info.clone(),
+                            destination_mint_holder_info.clone(),
+                            vault_info.clone(),
+                            token_info.clone()
+                        ],
+                        &[&[b"vault", &[bumps.vault]]],
+                    )?;
+                }
+
+                invoke_signed(
+                    &spl_token::instruction::close_account(
+                        token_info.key,
+                        vault_mint_info.key,
+                        payer.key,
+                        vault_info.key,
+                        &[],
+                    )?,
+                    &[
+                        vault_mint_info.clone(),
+                        payer.clone(),
+                        vault_info.clone(),
+                        token_info.clone()
+                    ],
+                    &[&[b"vault", &[bumps.vault]]],
+                )?;
+            }
+
+            // The rent top-up has to happen before the direct lamport moves: a CPI only balances its own accounts.
+            mark_vault_closed(payer, vault_info, system_program)?;
+            close_program_account(price_account_info, payer)?;
+            close_program_account(state_info, payer)?;
+            msg!("Sale closed: {} deposited, {} sold, {} withdrawn", state.deposited, state.sold, state.withdrawn);
+            emit(SellEvent::SaleClosed{destination, leftover})?;
+        },
+
+        SellInstruction::SetSaleEnd{end_ts}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Config)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let mut config = load_config_mut(program_id, payer, config_info, system_program, &bumps)?;
+
+            config.sale_end = end_ts;
+            write_account(config_info, &config)?;
+            emit(SellEvent::SaleEndSet{end_ts})?;
+        },
+
+        SellInstruction::SetPaused{paused}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Pause)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let mut config = load_config_mut(program_id, payer, config_info, system_program, &bumps)?;
+
+            config.paused = paused;
+            write_account(config_info, &config)?;
+            emit(SellEvent::PausedSet{paused})?;
+        },
+
+        SellInstruction::SetVoucherSigner{signer}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Config)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let mut config = load_config_mut(program_id, payer, config_info, system_program, &bumps)?;
+
+            config.voucher_signer = signer;
+            write_account(config_info, &config)?;
+            emit(SellEvent::VoucherSignerSet{signer})?;
+        },
+
+        SellInstruction::Block{wallet}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let entry_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Config)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let (entry_address, entry_bump) =
+                Pubkey::find_program_address(&["blocked".as_bytes(), wallet.as_ref()], program_id);
+            if *entry_info.key!=entry_address{
+                msg!("Wrong blocklist account");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            if entry_info.owner==program_id{
+                msg!("{:?} is already blocked", wallet);
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+
+            create_pda_account(
+                payer,
+                entry_info,
+                system_program,
+                &Rent::get()?,
+                BlockedWallet::SIZE,
+                program_id,
+                &[b"blocked", wallet.as_ref(), &[entry_bump]],
+            )?;
+            write_account(entry_info, &BlockedWallet{wallet, blocked_at: Clock::get()?.unix_timestamp})?;
+            emit(SellEvent::WalletBlocked{wallet})?;
+        },
+
+        SellInstruction::Unblock{wallet}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let entry_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Config)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if entry_info.owner!=program_id || read_account::<BlockedWallet>(entry_info)?.wallet!=wallet{
+                msg!("{:?} isn't blocked", wallet);
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            close_program_account(entry_info, payer)?;
+            emit(SellEvent::WalletUnblocked{wallet})?;
+        },
+
+        SellInstruction::SetTokenGate{mint: gate_mint, min_balance, cap_bps}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Config)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let mut config = load_config_mut(program_id, payer, config_info, system_program, &bumps)?;
+
+            config.gate_mint = gate_mint;
+            config.gate_min_balance = min_balance;
+            config.gate_cap_bps = cap_bps;
+            write_account(config_info, &config)?;
+            emit(SellEvent::TokenGateSet{mint: gate_mint, min_balance, cap_bps})?;
+        },
+
+        SellInstruction::SetRounds{mut rounds}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let price_account_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Price)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if rounds.len()>MAX_SALE_ROUNDS{
+                msg!("No more than {} rounds", MAX_SALE_ROUNDS);
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            check_pda(program_id, price_account_info, "price", bumps.price)?;
+            if price_account_info.owner!=program_id{
+                msg!("Wrong price account");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let now = Clock::get()?.unix_timestamp;
+            let mut price_data = read_account::<PriceData>(price_account_info)?;
+            price_data.apply_pending(now);
+
+            let config = load_config(program_id, config_info, &bumps)?;
+            let mut state = load_state(program_id, state_info, &bumps)?;
+            for (index, round) in rounds.iter().enumerate(){
+                if round.start_ts>=round.end_ts || round.allocation==0{
+                    msg!("Round {} needs a window and an allocation", index);
+                    return Err(ProgramError::InvalidArgument);
+                }
+                if index>0 && round.start_ts<rounds[index - 1].end_ts{
+                    msg!("Round {} starts before round {} ends", index, index - 1);
+                    return Err(ProgramError::InvalidArgument);
+                }
+
+                // A round's price is a price change like any other: it moves at most the change
+                // limit from the price before it, and buyers get the notice SchedulePrice gives.
+                let previous = if index==0 { price_data.price } else { rounds[index - 1].price };
+                config.check_price(previous, round.price)?;
+                let unchanged = matches!(state.rounds.get(index),
+                    Some(old) if old.start_ts==round.start_ts && old.price==round.price);
+                if !unchanged && round.start_ts<now.saturating_add(MIN_PRICE_DELAY){
+                    msg!("Round {} needs to start at least {} seconds from now to change its price", index, MIN_PRICE_DELAY);
+                    return Err(ProgramError::InvalidArgument);
+                }
+            }
+
+            // A round keeps what it sold as long as it keeps its place and start.
+            for (index, round) in rounds.iter_mut().enumerate(){
+                round.sold = state.rounds.get(index)
+                    .filter(|old| old.start_ts==round.start_ts)
+                    .map(|old| old.sold)
+                    .unwrap_or(0);
+            }
+            state.rounds = rounds.clone();
+            write_account(state_info, &state)?;
+            emit(SellEvent::RoundsSet{rounds})?;
+        },
+
+        SellInstruction::SetPurchaseLimits{min_purchase, lot_size}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Config)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let mut config = load_config_mut(program_id, payer, config_info, system_program, &bumps)?;
+
+            config.min_purchase = min_purchase;
+            config.lot_size = lot_size;
+            write_account(config_info, &config)?;
+            emit(SellEvent::PurchaseLimitsSet{min_purchase, lot_size})?;
+        },
+
+        SellInstruction::BurnUnsold=>{
+            let vault_info = next_account_info(accounts_iter)?;
+            let vault_mint_info = next_account_info(accounts_iter)?;
+            let mint_info = next_account_info(accounts_iter)?;
+            let token_info = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+            let vault = *vault_info.key;
+
+            if *mint_info.key!=mint{
+                msg!("Wrong mint");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, vault_info, "vault", bumps.vault)?;
+
+            if *vault_mint_info.key!=spl_associated_token_account::get_associated_token_address(&vault, &mint){
+                msg!("Wrong vault_mint_holder");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let config = load_config(program_id, config_info, &bumps)?;
+            let mut state = load_state(program_id, state_info, &bumps)?;
+
+            let now = Clock::get()?.unix_timestamp;
+            if config.sale_end==0 || now<config.sale_end{
+                msg!("Unsold tokens can only be burned after the sale end");
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            let unsold = spl_token::state::Account::unpack(&vault_mint_info.data.borrow())?.amount;
+
+            if unsold>0{
+                invoke_signed(
+                    &spl_token::instruction::burn(
+                        token_info.key,
+                        vault_mint_info.key,
+                        mint_info.key,
+                        vault_info.key,
+                        &[],
+                        unsold,
+                    )?,
+                    &[
+                        vault_mint_info.clone(),
+                        mint_info.clone(),
+                        vault_info.clone(),
+                        token_info.clone()
+                    ],
+                    &[&[b"vault", &[bumps.vault]]],
+                )?;
+            }
+
+            state.burned = state.burned.saturating_add(unsold);
+            write_account(state_info, &state)?;
+            emit(SellEvent::UnsoldBurned{amount: unsold, total_burned: state.burned})?;
+        },
+
+        SellInstruction::TakeMintAuthority{supply_cap}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let vault_info = next_account_info(accounts_iter)?;
+            let mint_info = next_account_info(accounts_iter)?;
+            let token_info = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_admin(program_id, admin_info, accounts, &bumps)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *mint_info.key!=mint{
+                msg!("Wrong mint");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, vault_info, "vault", bumps.vault)?;
+
+            let supply = spl_token::state::Mint::unpack(&mint_info.data.borrow())?.supply;
+            if supply_cap<supply{
+                msg!("Supply cap {} is below the current supply {}", supply_cap, supply);
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            invoke(
+                &spl_token::instruction::set_authority(
+                    token_info.key,
+                    mint_info.key,
+                    Some(vault_info.key),
+                    spl_token::instruction::AuthorityType::MintTokens,
+                    payer.key,
+                    &[],
+                )?,
+                &[
+                    mint_info.clone(),
+                    payer.clone(),
+                    token_info.clone()
+                ],
+            )?;
+
+            let mut config = load_config_mut(program_id, payer, config_info, system_program, &bumps)?;
+
+            config.mint_on_demand = true;
+            config.supply_cap = supply_cap;
+            write_account(config_info, &config)?;
+            emit(SellEvent::MintAuthorityTaken{supply_cap})?;
+        },
+
+        SellInstruction::ReleaseMintAuthority{new_authority}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let vault_info = next_account_info(accounts_iter)?;
+            let mint_info = next_account_info(accounts_iter)?;
+            let token_info = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_admin(program_id, admin_info, accounts, &bumps)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *mint_info.key!=mint{
+                msg!("Wrong mint");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, vault_info, "vault", bumps.vault)?;
+
+            invoke_signed(
+                &spl_token::instruction::set_authority(
+                    token_info.key,
+                    mint_info.key,
+                    Some(&new_authority),
+                    spl_token::instruction::AuthorityType::MintTokens,
+                    vault_info.key,
+                    &[],
+                )?,
+                &[
+                    mint_info.clone(),
+                    vault_info.clone(),
+                    token_info.clone()
+                ],
+                &[&[b"vault", &[bumps.vault]]],
+            )?;
+
+            let mut config = load_config(program_id, config_info, &bumps)?;
+            if config_info.owner==program_id{
+                config.mint_on_demand = false;
+                write_account(config_info, &config)?;
+            }
+            emit(SellEvent::MintAuthorityReleased{new_authority})?;
+        },
+
+        SellInstruction::SetAdmins{signers, threshold}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            // The current signer set has to approve its replacement.
+            check_admin(program_id, admin_info, accounts, &bumps)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if signers.is_empty() || signers.len()>MAX_ADMIN_SIGNERS{
+                msg!("Admin set needs between 1 and {} signers", MAX_ADMIN_SIGNERS);
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            if threshold==0 || threshold as usize>signers.len(){
+                msg!("Threshold has to be between 1 and {}", signers.len());
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            if signers.iter().enumerate().any(|(i, signer)| signers[..i].contains(signer)){
+                msg!("Admin signers have to be unique");
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            if admin_info.owner!=program_id{
+                create_pda_account(
+                    payer,
+                    admin_info,
+                    system_program,
+                    &Rent::get()?,
+                    AdminData::SIZE,
+                    program_id,
+                    &[b"admin", &[bumps.admin]],
+                )?;
+            }
+
+            // Delegated roles outlive a change of the signer set.
+            let roles = load_admins(program_id, admin_info, &bumps)?.roles;
+            write_account(admin_info, &AdminData{signers: signers.clone(), threshold, roles})?;
+            emit(SellEvent::AdminsSet{signers, threshold})?;
+        },
+
+        SellInstruction::GrantRole{wallet, role}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            // Only the admin set hands out roles, a role holder can't pass its own on.
+            check_admin(program_id, admin_info, accounts, &bumps)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let mut admins = load_admins(program_id, admin_info, &bumps)?;
+            match admins.roles.iter_mut().find(|grant| grant.wallet==wallet){
+                Some(grant)=>grant.roles |= role.bit(),
+                None=>{
+                    if admins.roles.len()>=MAX_ROLE_GRANTS{
+                        msg!("No more than {} wallets can hold roles", MAX_ROLE_GRANTS);
+                        return Err(ProgramError::InvalidArgument);
+                    }
+                    admins.roles.push(RoleGrant{wallet, roles: role.bit()});
+                }
+            }
+
+            if admin_info.owner!=program_id{
+                create_pda_account(
+                    payer,
+                    admin_info,
+                    system_program,
+                    &Rent::get()?,
+                    AdminData::SIZE,
+                    program_id,
+                    &[b"admin", &[bumps.admin]],
+                )?;
+            }
+
+            write_account(admin_info, &admins)?;
+            emit(SellEvent::RoleGranted{wallet, role})?;
+        },
+
+        SellInstruction::RevokeRole{wallet, role}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+            let _system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_admin(program_id, admin_info, accounts, &bumps)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let mut admins = load_admins(program_id, admin_info, &bumps)?;
+            if !admins.has_role(&wallet, role){
+                msg!("{:?} doesn't hold the {:?} role", wallet, role);
+                return Err(ProgramError::InvalidArgument);
+            }
+            for grant in admins.roles.iter_mut().filter(|grant| grant.wallet==wallet){
+                grant.roles &= !role.bit();
+            }
+            admins.roles.retain(|grant| grant.roles!=0);
+
+            write_account(admin_info, &admins)?;
+            emit(SellEvent::RoleRevoked{wallet, role})?;
+        },
+
+        SellInstruction::Generate=>{
+            // The one place that searches for bumps; they are cached in the state account below.
+            let bumps = Bumps::find(program_id);
+
+            let payer = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let pda = next_account_info(accounts_iter)?;
+            let treasury_info = next_account_info(accounts_iter)?;
+
+            let vault_mint_info = next_account_info(accounts_iter)?;
+            let mint_info = next_account_info(accounts_iter)?;
+            let token_info = next_account_info(accounts_iter)?;
+            let rent_info = next_account_info(accounts_iter)?;
+            let assoc_acccount_info = next_account_info(accounts_iter)?;
+
+            let price_account_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let treasury_wsol_info = next_account_info(accounts_iter)?;
+            let native_mint_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let rent = &Rent::from_account_info(rent_info)?;
+
+            check_admin(program_id, admin_info, accounts, &bumps)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            
+            check_pda(program_id, pda, "vault", bumps.vault)?;
+            check_pda(program_id, treasury_info, "treasury", bumps.treasury)?;
+
+            if *mint_info.key!=mint{
+                msg!("Wrong mint");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *vault_mint_info.key!=spl_associated_token_account::get_associated_token_address(pda.key, &mint){
+                msg!("Wrong vault_mint_holder");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, price_account_info, "price", bumps.price)?;
+            check_pda(program_id, state_info, "state", bumps.state)?;
+
+            if *treasury_wsol_info.key!=treasury_wsol_address(treasury_info.key){
+                msg!("Wrong treasury wSOL account");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            if *native_mint_info.key!=spl_token::native_mint::id(){
+                msg!("Wrong native mint");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            // Only the marker counts: vaults assigned by the original `Generate` have no state PDA
+            // either, and this is where they get one.
+            if pda.owner==program_id && is_closed_vault(&pda.data.borrow()){
+                msg!("Sale is closed");
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let mut created = 0;
+
+            if pda.owner!=program_id{
+                create_pda_account(
+                    payer,
+                    pda,
+                    system_program,
+                    rent,
+                    0,
+                    program_id,
+                    &[b"vault", &[bumps.vault]],
+                )?;
+                msg!("Address generated: {:?}", pda.key);
+                created += 1;
+            }
+
+            if treasury_info.owner!=program_id{
+                create_pda_account(
+                    payer,
+                    treasury_info,
+                    system_program,
+                    rent,
+                    TreasuryData::SIZE,
+                    program_id,
+                    &[b"treasury", &[bumps.treasury]],
+                )?;
+                write_account(treasury_info, &TreasuryData{total_raised: 0, total_withdrawn: 0})?;
+                msg!("Treasury generated: {:?}", treasury_info.key);
+                created += 1;
+            }
+
+            // Wrapped SOL payments land here. Creating it once keeps its rent off wSOL buyers.
+            if treasury_wsol_info.owner!=token_info.key{
+                create_token_account_if_missing(
+                    payer,
+                    treasury_info,
+                    treasury_wsol_info,
+                    native_mint_info,
+                    system_program,
+                    token_info,
+                    rent_info,
+                    assoc_acccount_info,
+                )?;
+                msg!("Treasury wSOL account generated: {:?}", treasury_wsol_info.key);
+                created += 1;
+            }
+
+            if vault_mint_info.owner!=token_info.key{
+                create_token_account_if_missing(
+                    payer,
+                    pda,
+                    vault_mint_info,
+                    mint_info,
+                    system_program,
+                    token_info,
+                    rent_info,
+                    assoc_acccount_info,
+                )?;
+                msg!("Vault token account generated: {:?}", vault_mint_info.key);
+                created += 1;
+            }
+
+            if price_account_info.owner!=program_id{
+                create_pda_account(
+                    payer,
+                    price_account_info,
+                    system_program,
+                    rent,
+                    PriceData::SIZE,
+                    program_id,
+                    &[b"price", &[bumps.price]],
+                )?;
+                write_account(price_account_info, &PriceData::default())?;
+                msg!("Price account generated: {:?}", price_account_info.key);
+                created += 1;
+            }
+
+            if state_info.owner!=program_id{
+                create_pda_account(
+                    payer,
+                    state_info,
+                    system_program,
+                    rent,
+                    SaleState::SIZE,
+                    program_id,
+                    &[b"state", &[bumps.state]],
+                )?;
+                write_account(state_info, &SaleState{bumps, ..SaleState::default()})?;
+                msg!("State account generated: {:?}", state_info.key);
+                created += 1;
+            }
+
+            if created==0{
+                msg!("Sale is already set up");
+            }
+            emit(SellEvent::Generated{admin: *payer.key, created})?;
+        },
+
+        SellInstruction::WithdrawProceeds{lamports, destination}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let treasury_info = next_account_info(accounts_iter)?;
+            let destination_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Withdraw)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, treasury_info, "treasury", bumps.treasury)?;
+            if treasury_info.owner!=program_id{
+                msg!("Wrong treasury");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *destination_info.key!=destination{
+                msg!("Wrong destination");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let rent_reserve = Rent::get()?.minimum_balance(treasury_info.data_len());
+            let available = treasury_info.lamports().saturating_sub(rent_reserve);
+            if lamports>available{
+                msg!("Only {} lamports can be withdrawn from treasury", available);
+                return Err(ProgramError::InsufficientFunds);
+            }
+
+            let mut treasury_data = read_account::<TreasuryData>(treasury_info)?;
+            treasury_data.total_withdrawn = treasury_data.total_withdrawn.saturating_add(lamports);
+
+            **treasury_info.try_borrow_mut_lamports()? -= lamports;
+            **destination_info.try_borrow_mut_lamports()? += lamports;
+
+            write_account(treasury_info, &treasury_data)?;
+            emit(SellEvent::ProceedsWithdrawn{destination, lamports})?;
+        },
+
+        SellInstruction::UnwrapProceeds=>{
+            let payer = next_account_info(accounts_iter)?;
+            let treasury_info = next_account_info(accounts_iter)?;
+            let treasury_wsol_info = next_account_info(accounts_iter)?;
+            let unwrap_info = next_account_info(accounts_iter)?;
+            let native_mint_info = next_account_info(accounts_iter)?;
+            let token_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let rent_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Withdraw)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, treasury_info, "treasury", bumps.treasury)?;
+            if treasury_info.owner!=program_id{
+                msg!("Wrong treasury");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *treasury_wsol_info.key!=treasury_wsol_address(treasury_info.key){
+                msg!("Wrong treasury wSOL account");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *token_info.key!=spl_token::id(){
+                msg!("Wrong token program");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *native_mint_info.key!=spl_token::native_mint::id(){
+                msg!("Wrong native mint");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, unwrap_info, "unwrap", bumps.unwrap)?;
+
+            let lamports = spl_token::state::Account::unpack(&treasury_wsol_info.data.borrow())?.amount;
+            if lamports==0{
+                msg!("No wrapped SOL to unwrap");
+                return Err(ProgramError::InsufficientFunds);
+            }
+
+            // Closing a native account hands over its rent along with the wrapped amount, so the
+            // treasury's ATA stays open for the next wSOL buyer. The proceeds move to a temporary
+            // account the payer funds, whose closing unwraps them into the treasury PDA.
+            create_pda_account(
+                payer,
+                unwrap_info,
+                system_program,
+                &Rent::from_account_info(rent_info)?,
+                spl_token::state::Account::LEN,
+                token_info.key,
+                &[b"unwrap", &[bumps.unwrap]],
+            )?;
+            invoke(
+                &spl_token::instruction::initialize_account(
+                    token_info.key,
+                    unwrap_info.key,
+                    native_mint_info.key,
+                    treasury_info.key,
+                )?,
+                &[
+                    unwrap_info.clone(),
+                    native_mint_info.clone(),
+                    treasury_info.clone(),
+                    rent_info.clone(),
+                    token_info.clone(),
+                ],
+            )?;
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    token_info.key,
+                    treasury_wsol_info.key,
+                    unwrap_info.key,
+                    treasury_info.key,
+                    &[],
+                    lamports,
+                )?,
+                &[
+                    treasury_wsol_info.clone(),
+                    unwrap_info.clone(),
+                    treasury_info.clone(),
+                    token_info.clone(),
+                ],
+                &[&[b"treasury", &[bumps.treasury]]],
+            )?;
+
+            let deposit = unwrap_info.lamports() - lamports;
+            invoke_signed(
+                &spl_token::instruction::close_account(
+                    token_info.key,
+                    unwrap_info.key,
+                    treasury_info.key,
+                    treasury_info.key,
+                    &[],
+                )?,
+                &[
+                    unwrap_info.clone(),
+                    treasury_info.clone(),
+                    token_info.clone(),
+                ],
+                &[&[b"treasury", &[bumps.treasury]]],
+            )?;
+
+            // The payer gets back the rent deposit that came along.
+            **treasury_info.try_borrow_mut_lamports()? -= deposit;
+            **payer.try_borrow_mut_lamports()? += deposit;
+            emit(SellEvent::ProceedsUnwrapped{lamports})?;
+        },
+
+        SellInstruction::WithdrawLamports{lamports, destination}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let vault_info = next_account_info(accounts_iter)?;
+            let destination_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Withdraw)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            check_pda(program_id, vault_info, "vault", bumps.vault)?;
+            if vault_info.owner!=program_id{
+                msg!("Vault isn't generated");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if *destination_info.key!=destination{
+                msg!("Wrong destination");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            // The vault stays rent-exempt: it outlives the sale to carry `CLOSED_VAULT_MARKER`.
+            let rent_reserve = Rent::get()?.minimum_balance(vault_info.data_len()).max(1);
+            let available = vault_info.lamports().saturating_sub(rent_reserve);
+            if lamports>available{
+                msg!("Only {} lamports can be withdrawn from vault", available);
+                return Err(ProgramError::InsufficientFunds);
+            }
+
+            **vault_info.try_borrow_mut_lamports()? -= lamports;
+            **destination_info.try_borrow_mut_lamports()? += lamports;
+
+            emit(SellEvent::VaultLamportsWithdrawn{destination, lamports})?;
+        },
+
+        SellInstruction::SetSplit{recipients, dust_recipient}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Withdraw)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            if recipients.is_empty() || recipients.len()>MAX_SPLIT_RECIPIENTS{
+                msg!("Split needs between 1 and {} recipients", MAX_SPLIT_RECIPIENTS);
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            for (index, share) in recipients.iter().enumerate(){
+                if share.bps==0{
+                    msg!("Split recipient {:?} has no share", share.wallet);
+                    return Err(ProgramError::InvalidArgument);
+                }
+                if recipients[..index].iter().any(|other| other.wallet==share.wallet){
+                    msg!("Split recipient {:?} is listed twice", share.wallet);
+                    return Err(ProgramError::InvalidArgument);
+                }
+            }
 
-// Program entrypoint's implementation
-pub fn process_instruction(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8],
-) -> ProgramResult {
-    msg!("author: Rusty 0ne <4fun.and.job.offers@gmail.com>");
+            let total_bps = recipients.iter().fold(0u32, |sum, share| sum + share.bps as u32);
+            if total_bps!=TOTAL_BPS as u32{
+                msg!("Split shares sum to {} bps instead of {}", total_bps, TOTAL_BPS);
+                return Err(ProgramError::InvalidArgument);
+            }
 
-    let accounts_iter = &mut accounts.iter();
-    let instruction: SellInstruction = try_from_slice_unchecked(instruction_data).unwrap();
+            if !recipients.iter().any(|share| share.wallet==dust_recipient){
+                msg!("Dust recipient has to be one of the split recipients");
+                return Err(ProgramError::InvalidArgument);
+            }
 
-    let admin = "Ek6Vqf4cCq6zXAp9TwSqeAbQXm8Eo3Y8DV7abbJYntwv".parse::<Pubkey>().unwrap();
-    let treasury = "D4bMaJQG5EhGExPqT1tYR8Vyd39Gf2tzN4JoxAy42jgP".parse::<Pubkey>().unwrap();
-    let mint = "5CZJ7e4uUWCogW2z7rvcE2yur6yE7Z7kcGYrFqLLXSL9".parse::<Pubkey>().unwrap();
+            let mut config = load_config_mut(program_id, payer, config_info, system_program, &bumps)?;
 
-    match instruction{
-        SellInstruction::SetPrice{price}=>{
-            let payer = next_account_info(accounts_iter)?;
-            let price_account_info = next_account_info(accounts_iter)?;
-            let sys_info = next_account_info(accounts_iter)?;
-            let rent_info = next_account_info(accounts_iter)?;
+            config.split = recipients.clone();
+            config.dust_recipient = dust_recipient;
+            write_account(config_info, &config)?;
+            emit(SellEvent::SplitSet{recipients, dust_recipient})?;
+        },
 
-            let rent = &Rent::from_account_info(rent_info)?;
+        SellInstruction::DistributeProceeds=>{
+            let treasury_info = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
 
-            let (price_address, price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
-            
-            let price_data = PriceData{price};
-            
-            if *payer.key!=admin{
-                msg!("Unauthorized access");
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_pda(program_id, treasury_info, "treasury", bumps.treasury)?;
+            if treasury_info.owner!=program_id{
+                msg!("Wrong treasury");
                 return Err(ProgramError::IncorrectProgramId);
             }
-            if *price_account_info.key!=price_address{
-                msg!("Wrong price account");
-                return Err(ProgramError::IncorrectProgramId);
+
+            let config = load_config(program_id, config_info, &bumps)?;
+            if config.split.is_empty(){
+                msg!("Proceeds split isn't configured");
+                return Err(ProgramError::InvalidAccountData);
             }
 
-            if !payer.is_signer{
-                msg!("Admin didn't sign transaction");
-                return Err(ProgramError::IncorrectProgramId);
+            let rent_reserve = Rent::get()?.minimum_balance(treasury_info.data_len());
+            let available = treasury_info.lamports().saturating_sub(rent_reserve);
+            if available==0{
+                msg!("Nothing to distribute");
+                return Err(ProgramError::InsufficientFunds);
             }
 
-            let size = 8;
-            if price_account_info.owner != program_id{
-                let required_lamports = rent
-                    .minimum_balance(size)
-                    .max(1)
-                    .saturating_sub(price_account_info.lamports());
+            let mut recipient_infos = Vec::with_capacity(config.split.len());
+            for share in config.split.iter(){
+                let recipient_info = next_account_info(accounts_iter)?;
+                if *recipient_info.key!=share.wallet{
+                    msg!("Wrong split recipient {:?}", recipient_info.key);
+                    return Err(ProgramError::InvalidArgument);
+                }
+                recipient_infos.push(recipient_info);
+            }
 
-                invoke(
-                    &system_instruction::transfer(payer.key, price_account_info.key, required_lamports),
-                    &[
-                        payer.clone(),
-                        price_account_info.clone(),
-                        sys_info.clone(),
-                    ],
-                )?;
-                invoke_signed(
-                    &system_instruction::allocate(price_account_info.key, size as u64),
-                    &[
-                        price_account_info.clone(),
-                        sys_info.clone(),
-                    ],
-                    &[&[b"price", &[price_bump]]],
-                )?;
+            let mut distributed = 0u64;
+            for (share, recipient_info) in config.split.iter().zip(recipient_infos.iter()){
+                let cut = (available as u128 * share.bps as u128 / TOTAL_BPS as u128) as u64;
+                **recipient_info.try_borrow_mut_lamports()? += cut;
+                distributed += cut;
+            }
 
-                invoke_signed(
-                    &system_instruction::assign(price_account_info.key, program_id),
-                    &[
-                        price_account_info.clone(),
-                        sys_info.clone(),
-                    ],
-                    &[&[b"price", &[price_bump]]],
-                )?;
+            let dust = available - distributed;
+            if dust>0{
+                let dust_info = recipient_infos
+                    .iter()
+                    .find(|info| *info.key==config.dust_recipient)
+                    .ok_or(ProgramError::InvalidAccountData)?;
+                **dust_info.try_borrow_mut_lamports()? += dust;
             }
 
-            price_data.serialize(&mut &mut price_account_info.data.borrow_mut()[..])?;
+            **treasury_info.try_borrow_mut_lamports()? -= available;
 
-        }
-        SellInstruction::Withdraw{amount}=>{
+            let mut treasury_data = read_account::<TreasuryData>(treasury_info)?;
+            treasury_data.total_withdrawn = treasury_data.total_withdrawn.saturating_add(available);
+            write_account(treasury_info, &treasury_data)?;
+            emit(SellEvent::ProceedsDistributed{lamports: available, dust})?;
+        },
+
+        SellInstruction::SetReferralBps{bps}=>{
             let payer = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
             let system_program = next_account_info(accounts_iter)?;
-            let vault_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
 
-            let vault_mint_info = next_account_info(accounts_iter)?;
-            let mint_info = next_account_info(accounts_iter)?;
-            let token_info = next_account_info(accounts_iter)?;
-            let rent_info = next_account_info(accounts_iter)?;
-            let assoc_acccount_info = next_account_info(accounts_iter)?;
+            let bumps = load_bumps(program_id, state_info)?;
 
-            let treasury_info = next_account_info(accounts_iter)?;
-            let payer_mint_holder_info = next_account_info(accounts_iter)?;
+            check_role(program_id, admin_info, accounts, &bumps, Role::Withdraw)?;
 
-            if *payer.key!=admin{
-                msg!("Unauthorized access");
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
                 return Err(ProgramError::IncorrectProgramId);
             }
 
-            if *treasury_info.key!=treasury{
-                msg!("Wrong treasury");
-                return Err(ProgramError::IncorrectProgramId);
+            if bps>TOTAL_BPS{
+                msg!("Referral share can't exceed {} bps", TOTAL_BPS);
+                return Err(ProgramError::InvalidArgument);
             }
 
+            let mut config = load_config_mut(program_id, payer, config_info, system_program, &bumps)?;
+
+            config.referral_bps = bps;
+            write_account(config_info, &config)?;
+            emit(SellEvent::ReferralBpsSet{bps})?;
+        },
+
+        SellInstruction::SetPriceBounds{floor, ceiling, max_change_bps, window_secs, max_window_change_bps}=>{
+            let payer = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let price_account_info = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Config)?;
+
             if !payer.is_signer{
-                msg!("Admin didn't sign transaction");
+                msg!("Payer didn't sign transaction");
                 return Err(ProgramError::IncorrectProgramId);
             }
 
-            if *mint_info.key!=mint{
-                msg!("Wrong treasury");
-                return Err(ProgramError::IncorrectProgramId);
+            if ceiling==0 || floor>ceiling{
+                msg!("Price bounds need a ceiling above 0 and a floor not above it");
+                return Err(ProgramError::InvalidArgument);
             }
 
+            if max_change_bps>TOTAL_BPS || max_window_change_bps>TOTAL_BPS{
+                msg!("Price change limit can't exceed {} bps", TOTAL_BPS);
+                return Err(ProgramError::InvalidArgument);
+            }
 
-            let payer_mint_holder = spl_associated_token_account::get_associated_token_address(payer.key, &mint);
-            if *payer_mint_holder_info.key!=payer_mint_holder{
-                msg!("Wrong payer_mint_holder");
-                return Err(ProgramError::IncorrectProgramId);
+            if window_secs<0{
+                msg!("Price window can't be negative");
+                return Err(ProgramError::InvalidArgument);
             }
 
-            let (_vault, vault_bump) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+            let mut config = load_config_mut(program_id, payer, config_info, system_program, &bumps)?;
+            if config.price_ceiling>0{
+                msg!("Price bounds are already set and can't be changed");
+                return Err(SellError::PriceBoundsLocked.into());
+            }
 
-            if payer_mint_holder_info.owner != token_info.key{
-                invoke(
-                    &spl_associated_token_account::create_associated_token_account(
-                        payer.key,
-                        payer.key,
-                        mint_info.key,
-                    ),
-                    &[
-                        payer.clone(), 
-                        payer_mint_holder_info.clone(), 
-                        payer.clone(),
-                        mint_info.clone(),
-                        system_program.clone(),
-                        token_info.clone(),
-                        rent_info.clone(),
-                        assoc_acccount_info.clone(),
-                    ],
-                    
-                )?;
+            config.price_floor = floor;
+            config.price_ceiling = ceiling;
+            config.max_price_change_bps = max_change_bps;
+            config.price_window = window_secs;
+            config.max_window_change_bps = max_window_change_bps;
+
+            // Bounds that exclude the price already set, or already scheduled, would never bind it.
+            check_pda(program_id, price_account_info, "price", bumps.price)?;
+            if price_account_info.owner==program_id{
+                let price_data = read_account::<PriceData>(price_account_info)?;
+                for price in [price_data.price, price_data.pending_price]{
+                    if price>0{
+                        config.check_bounds(price)?;
+                    }
+                }
             }
 
-            invoke_signed(
-                &spl_token::instruction::transfer(
-                    token_info.key,
-                    vault_mint_info.key,
-                    payer_mint_holder_info.key,
-                    vault_info.key,
-                    &[],
-                    amount,
-                )?,
-                &[
-                    vault_mint_info.clone(),
-                    payer_mint_holder_info.clone(),
-                    vault_info.clone(), 
-                    token_info.clone()
-                ],
-                &[&[b"vault", &[vault_bump]]],
-            )?;
+            write_account(config_info, &config)?;
+            emit(SellEvent::PriceBoundsSet{floor, ceiling, max_change_bps})?;
+            if window_secs>0{
+                emit(SellEvent::PriceWindowSet{window_secs, max_change_bps: max_window_change_bps})?;
+            }
         },
 
-        SellInstruction::Generate=>{
-            let (vault_pda, vault_bump_seed) =
-                Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
-
+        SellInstruction::SetLimitTarget{target}=>{
             let payer = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
             let system_program = next_account_info(accounts_iter)?;
-            let pda = next_account_info(accounts_iter)?;
-            
-            if pda.key!=&vault_pda{
-                msg!("Wrong account generated by client");
+            let state_info = next_account_info(accounts_iter)?;
+            let admin_info = next_account_info(accounts_iter)?;
+
+            let bumps = load_bumps(program_id, state_info)?;
+
+            check_role(program_id, admin_info, accounts, &bumps, Role::Config)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
                 return Err(ProgramError::IncorrectProgramId);
             }
 
-            if pda.owner==program_id{
-                msg!("Account already assigned");
+            let mut config = load_config_mut(program_id, payer, config_info, system_program, &bumps)?;
+
+            config.limit_target = target;
+            write_account(config_info, &config)?;
+            emit(SellEvent::LimitTargetSet{target})?;
+        },
+
+        // Permissionless: it only re-encodes accounts, the payer just covers any extra rent.
+        SellInstruction::Migrate=>{
+            let payer = next_account_info(accounts_iter)?;
+            let system_program = next_account_info(accounts_iter)?;
+
+            if !payer.is_signer{
+                msg!("Payer didn't sign transaction");
                 return Err(ProgramError::IncorrectProgramId);
             }
 
-            invoke(
-                &system_instruction::transfer(payer.key, &vault_pda, 2_282_880),
-                &[
-                    payer.clone(),
-                    pda.clone(),
-                    system_program.clone(),
-                ],
-            )?;
+            let rent = &Rent::get()?;
+            let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], program_id);
+            let (treasury_address, _treasury_bump) = Pubkey::find_program_address(&["treasury".as_bytes()], program_id);
+            let (config_address, _config_bump) = Pubkey::find_program_address(&["config".as_bytes()], program_id);
+            let (state_address, _state_bump) = Pubkey::find_program_address(&["state".as_bytes()], program_id);
+            let (admin_address, _admin_bump) = Pubkey::find_program_address(&["admin".as_bytes()], program_id);
 
-            invoke_signed(
-                &system_instruction::assign(&vault_pda, program_id),
-                &[
-                    pda.clone(),
-                    system_program.clone(),
-                ],
-                &[&[b"vault", &[vault_bump_seed]]],
-            )?;
-            msg!("Address generated: {:?}", vault_pda);
+            // Every remaining account is migrated to the layout its address says it holds.
+            for account in accounts_iter{
+                if account.owner!=program_id{
+                    msg!("{} isn't owned by the program", account.key);
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+
+                let (migrated, version) = if *account.key==price_address{
+                    (migrate_account::<PriceData>(payer, account, system_program, rent)?, PriceData::VERSION)
+                } else if *account.key==treasury_address{
+                    (migrate_account::<TreasuryData>(payer, account, system_program, rent)?, TreasuryData::VERSION)
+                } else if *account.key==config_address{
+                    (migrate_account::<SaleConfig>(payer, account, system_program, rent)?, SaleConfig::VERSION)
+                } else if *account.key==state_address{
+                    let migrated = migrate_account::<SaleState>(payer, account, system_program, rent)?;
+                    let mut state = read_account::<SaleState>(account)?;
+                    if state.bumps.state==0 || state.bumps.unwrap==0{
+                        state.bumps = Bumps::find(program_id);
+                        write_account(account, &state)?;
+                    }
+                    (migrated, SaleState::VERSION)
+                } else if *account.key==admin_address{
+                    (migrate_account::<AdminData>(payer, account, system_program, rent)?, AdminData::VERSION)
+                } else if account.data.borrow().starts_with(&RoundPurchase::DISCRIMINATOR){
+                    // Round purchases are keyed by the round and wallet they store; their layouts
+                    // have always been versioned.
+                    let (wallet, round) = {
+                        let data = account.data.borrow();
+                        if data.len()<ACCOUNT_HEADER_LEN + 33{
+                            msg!("Unknown program account {}", account.key);
+                            return Err(ProgramError::InvalidAccountData);
+                        }
+                        (Pubkey::new(&data[ACCOUNT_HEADER_LEN..ACCOUNT_HEADER_LEN + 32]), data[ACCOUNT_HEADER_LEN + 32])
+                    };
+                    let (purchase_address, _purchase_bump) = Pubkey::find_program_address(&["round".as_bytes(), &[round], wallet.as_ref()], program_id);
+                    if *account.key!=purchase_address{
+                        msg!("Unknown program account {}", account.key);
+                        return Err(ProgramError::InvalidAccountData);
+                    }
+                    (migrate_account::<RoundPurchase>(payer, account, system_program, rent)?, RoundPurchase::VERSION)
+                } else {
+                    // Referrer PDAs are keyed by the wallet stored at the start of their body.
+                    let referrer = {
+                        let data = account.data.borrow();
+                        if data.len()<ACCOUNT_HEADER_LEN + 32 || data[..8]!=ReferrerData::DISCRIMINATOR{
+                            msg!("Unknown program account {}", account.key);
+                            return Err(ProgramError::InvalidAccountData);
+                        }
+                        Pubkey::new(&data[ACCOUNT_HEADER_LEN..ACCOUNT_HEADER_LEN + 32])
+                    };
+                    let (referrer_address, _referrer_bump) = Pubkey::find_program_address(&["referrer".as_bytes(), referrer.as_ref()], program_id);
+                    if *account.key!=referrer_address{
+                        msg!("Unknown program account {}", account.key);
+                        return Err(ProgramError::InvalidAccountData);
+                    }
+                    (migrate_account::<ReferrerData>(payer, account, system_program, rent)?, ReferrerData::VERSION)
+                };
+
+                if migrated{
+                    msg!("Migrated {:?} to version {}", account.key, version);
+                    emit(SellEvent::AccountMigrated{account: *account.key, version})?;
+                }
+            }
         },
 
-        SellInstruction::Buy{amount}=>{
+        SellInstruction::Buy{amount: requested, proof, fill_mode, payment, blocklist_bumps}=>{
             let payer = next_account_info(accounts_iter)?;
             let system_program = next_account_info(accounts_iter)?;
             let vault_info = next_account_info(accounts_iter)?;
@@ -251,17 +3124,21 @@ pub fn process_instruction(
             let assoc_acccount_info = next_account_info(accounts_iter)?;
 
             let treasury_info = next_account_info(accounts_iter)?;
-            let payer_mint_holder_info = next_account_info(accounts_iter)?;
+            // The payer pays for everything, tokens go to the recipient, which may be the payer itself.
+            let recipient_info = next_account_info(accounts_iter)?;
+            let recipient_mint_holder_info = next_account_info(accounts_iter)?;
             let price_data_info = next_account_info(accounts_iter)?;
+            let config_info = next_account_info(accounts_iter)?;
+            let state_info = next_account_info(accounts_iter)?;
+            // ["blocked", wallet] PDAs of both sides, whether or not they exist.
+            let payer_block_info = next_account_info(accounts_iter)?;
+            let recipient_block_info = next_account_info(accounts_iter)?;
 
-            let (price_address, _price_bump) = Pubkey::find_program_address(&["price".as_bytes()], &program_id);
+            let bumps = load_bumps(program_id, state_info)?;
 
-            if *price_data_info.key!=price_address{
-                msg!("Wrong price_data_info");
-                return Err(ProgramError::IncorrectProgramId);
-            }
-
-            if *treasury_info.key!=treasury{
+            check_pda(program_id, price_data_info, "price", bumps.price)?;
+            check_pda(program_id, treasury_info, "treasury", bumps.treasury)?;
+            if treasury_info.owner!=program_id{
                 msg!("Wrong treasury");
                 return Err(ProgramError::IncorrectProgramId);
             }
@@ -271,72 +3148,392 @@ pub fn process_instruction(
                 return Err(ProgramError::IncorrectProgramId);
             }
 
+            check_not_blocked(program_id, payer.key, payer_block_info, blocklist_bumps[0])?;
+            check_not_blocked(program_id, recipient_info.key, recipient_block_info, blocklist_bumps[1])?;
+
             if *mint_info.key!=mint{
                 msg!("Wrong treasury");
                 return Err(ProgramError::IncorrectProgramId);
             }
 
-            let payer_mint_holder = spl_associated_token_account::get_associated_token_address(payer.key, &mint);
-            if *payer_mint_holder_info.key!=payer_mint_holder{
-                msg!("Wrong payer_mint_holder");
+            let recipient_mint_holder = spl_associated_token_account::get_associated_token_address(recipient_info.key, &mint);
+            if *recipient_mint_holder_info.key!=recipient_mint_holder{
+                msg!("Wrong recipient_mint_holder");
                 return Err(ProgramError::IncorrectProgramId);
             }
-            let price_data = if let Ok(data) = PriceData::try_from_slice(&price_data_info.data.borrow()){
-                data
+            let mut price_data = match read_account::<PriceData>(price_data_info){
+                Ok(data)=>data,
+                Err(err) if price_data_info.owner==program_id=>return Err(err),
+                Err(_)=>{
+                    msg!("Price didn't set");
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+            };
+
+            if price_data.apply_pending(Clock::get()?.unix_timestamp){
+                write_account(price_data_info, &price_data)?;
+                emit(SellEvent::PriceSet{price: price_data.price})?;
+            }
+
+            let config = load_config(program_id, config_info, &bumps)?;
+            let mut state = load_state(program_id, state_info, &bumps)?;
+
+            if config.paused{
+                msg!("Sale is paused");
+                return Err(SellError::SalePaused.into());
+            }
+
+            if config.sale_end>0 && Clock::get()?.unix_timestamp>=config.sale_end{
+                msg!("Sale has ended");
+                return Err(ProgramError::InvalidArgument);
+            }
+
+            // What can be delivered: the vault's balance, or what the supply cap leaves when minting on demand.
+            let available = if config.mint_on_demand{
+                let supply = spl_token::state::Mint::unpack(&mint_info.data.borrow())?.supply;
+                config.supply_cap.saturating_sub(supply)
+            } else {
+                let vault_account = spl_token::state::Account::unpack(&vault_mint_info.data.borrow())?;
+                if vault_account.owner!=*vault_info.key || vault_account.mint!=mint{
+                    msg!("Wrong vault_mint_holder");
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+                vault_account.amount
+            };
+
+            let amount = if requested<=available{
+                requested
+            } else {
+                match fill_mode{
+                    FillMode::AllOrNothing=>{
+                        msg!("Only {} of {} tokens are available", available, requested);
+                        return Err(SellError::InsufficientInventory.into());
+                    }
+                    FillMode::Partial=>{
+                        let fill = if config.lot_size>1 { available - available % config.lot_size } else { available };
+                        if fill==0{
+                            msg!("No tokens are available");
+                            return Err(SellError::InsufficientInventory.into());
+                        }
+                        msg!("Filling {} of {} tokens", fill, requested);
+                        fill
+                    }
+                }
+            };
+
+            // Before anything below moves lamports, be it for rent or the purchase itself.
+            config.check_amount(amount)?;
+
+            // Accounts that depend on the config follow the blocklist PDAs: the buyer's gate-mint
+            // token account if the sale is gated, then the instructions sysvar and the voucher's
+            // nonce PDA if it needs vouchers.
+            if config.gate_mint!=Pubkey::default(){
+                let gate_info = next_account_info(accounts_iter)?;
+                check_token_gate(&config, config.limited_wallet(payer.key, recipient_info.key), gate_info, amount)?;
+            }
+
+            if config.voucher_signer!=Pubkey::default(){
+                let instructions_info = next_account_info(accounts_iter)?;
+                let voucher_nonce_info = next_account_info(accounts_iter)?;
+
+                let voucher = verified_voucher(program_id, instructions_info, &config.voucher_signer)?;
+                let buyer = config.limited_wallet(payer.key, recipient_info.key);
+                if voucher.buyer!=*buyer{
+                    msg!("Voucher is for {:?}, not {:?}", voucher.buyer, buyer);
+                    return Err(SellError::InvalidVoucher.into());
+                }
+                if Clock::get()?.unix_timestamp>=voucher.expiry{
+                    msg!("Voucher expired at {}", voucher.expiry);
+                    return Err(SellError::VoucherExpired.into());
+                }
+                if amount>voucher.max_amount{
+                    msg!("Voucher allows at most {} tokens", voucher.max_amount);
+                    return Err(SellError::VoucherAmountExceeded.into());
+                }
+
+                let nonce_bytes = voucher.nonce.to_le_bytes();
+                let (nonce_address, nonce_bump) =
+                    Pubkey::find_program_address(&["voucher".as_bytes(), buyer.as_ref(), &nonce_bytes], program_id);
+                if *voucher_nonce_info.key!=nonce_address{
+                    msg!("Wrong voucher nonce account");
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+                if voucher_nonce_info.owner==program_id{
+                    msg!("Voucher {} was already used", voucher.nonce);
+                    return Err(SellError::VoucherUsed.into());
+                }
+                create_pda_account(
+                    payer,
+                    voucher_nonce_info,
+                    system_program,
+                    &Rent::get()?,
+                    0,
+                    program_id,
+                    &[b"voucher", buyer.as_ref(), &nonce_bytes, &[nonce_bump]],
+                )?;
+                emit(SellEvent::VoucherRedeemed{buyer: *buyer, nonce: voucher.nonce, amount})?;
+            }
+
+            // While rounds are configured the active one sets the price and limits. A round with
+            // a wallet cap takes the buyer's ["round", [index], wallet] PDA after the voucher accounts.
+            let active_round = if state.rounds.is_empty(){
+                None
             } else {
+                let index = match state.active_round(Clock::get()?.unix_timestamp){
+                    Some(index)=>index,
+                    None=>{
+                        msg!("No sale round is open");
+                        return Err(SellError::NoActiveRound.into());
+                    }
+                };
+                let round = &state.rounds[index];
+                let buyer = config.limited_wallet(payer.key, recipient_info.key);
+
+                if round.allowlist_root!=[0; 32] && !verify_allowlist(&round.allowlist_root, buyer, &proof){
+                    msg!("{:?} isn't on the round {} allowlist", buyer, index);
+                    return Err(SellError::NotAllowlisted.into());
+                }
+
+                if round.sold.saturating_add(amount)>round.allocation{
+                    msg!("Round {} has {} tokens left", index, round.allocation.saturating_sub(round.sold));
+                    return Err(SellError::RoundAllocationExceeded.into());
+                }
+
+                if round.wallet_cap>0{
+                    let purchase_info = next_account_info(accounts_iter)?;
+                    let purchase = if purchase_info.owner!=program_id{
+                        let (purchase_address, purchase_bump) = Pubkey::find_program_address(
+                            &["round".as_bytes(), &[index as u8], buyer.as_ref()],
+                            program_id,
+                        );
+                        if *purchase_info.key!=purchase_address{
+                            msg!("Wrong round purchase account");
+                            return Err(ProgramError::IncorrectProgramId);
+                        }
+                        create_pda_account(
+                            payer,
+                            purchase_info,
+                            system_program,
+                            &Rent::get()?,
+                            RoundPurchase::SIZE,
+                            program_id,
+                            &[b"round", &[index as u8], buyer.as_ref(), &[purchase_bump]],
+                        )?;
+                        RoundPurchase{wallet: *buyer, round: index as u8, bought: 0, start_ts: round.start_ts}
+                    } else {
+                        // Accounts from before `start_ts` was kept get it as 0, so they start over below.
+                        migrate_account::<RoundPurchase>(payer, purchase_info, system_program, &Rent::get()?)?;
+                        // Like referrer data, the stored wallet and round prove the address.
+                        let purchase = read_account::<RoundPurchase>(purchase_info)?;
+                        if purchase.wallet!=*buyer || purchase.round!=index as u8{
+                            msg!("Wrong round purchase account");
+                            return Err(ProgramError::IncorrectProgramId);
+                        }
+                        if purchase.start_ts!=round.start_ts{
+                            RoundPurchase{bought: 0, start_ts: round.start_ts, ..purchase}
+                        } else {
+                            purchase
+                        }
+                    };
+
+                    if purchase.bought.saturating_add(amount)>round.wallet_cap{
+                        msg!("{:?} can buy {} more tokens in round {}", buyer, round.wallet_cap.saturating_sub(purchase.bought), index);
+                        return Err(SellError::RoundWalletCapExceeded.into());
+                    }
+                    write_account(purchase_info, &RoundPurchase{bought: purchase.bought + amount, ..purchase})?;
+                }
+
+                Some(index)
+            };
+
+            // Paying in wrapped SOL takes the payer's wSOL token account and the treasury's wSOL
+            // ATA next. The token program checks that both hold the native mint.
+            let wsol_infos = match payment{
+                PaymentSource::Lamports=>None,
+                PaymentSource::WrappedSol=>{
+                    let payer_wsol_info = next_account_info(accounts_iter)?;
+                    let treasury_wsol_info = next_account_info(accounts_iter)?;
+                    if *treasury_wsol_info.key!=treasury_wsol_address(treasury_info.key){
+                        msg!("Wrong treasury wSOL account");
+                        return Err(ProgramError::IncorrectProgramId);
+                    }
+                    Some((payer_wsol_info, treasury_wsol_info))
+                }
+            };
+
+            let price = match active_round{
+                Some(index)=>state.rounds[index].price,
+                None=>price_data.price,
+            };
+            if price==0{
                 msg!("Price didn't set");
                 return Err(ProgramError::IncorrectProgramId);
+            }
+
+            // Optional trailing accounts: referrer wallet, its ["referrer", referrer] PDA and its
+            // blocklist PDA, plus a wSOL token account of the referrer's when paying in wrapped SOL.
+            let referrer_infos = match (accounts_iter.next(), accounts_iter.next(), accounts_iter.next()){
+                (Some(referrer_info), Some(referrer_data_info), Some(referrer_block_info))=>{
+                    check_not_blocked(program_id, referrer_info.key, referrer_block_info, blocklist_bumps[2])?;
+                    Some((referrer_info, referrer_data_info))
+                }
+                (None, None, None)=>None,
+                _=>return Err(ProgramError::NotEnoughAccountKeys),
+            };
+            let referrer_wsol_info = match (referrer_infos, wsol_infos){
+                (Some((referrer_info, _)), Some(_))=>{
+                    let referrer_wsol_info = next_account_info(accounts_iter)?;
+                    if referrer_wsol_info.owner!=token_info.key
+                        || spl_token::state::Account::unpack(&referrer_wsol_info.data.borrow())?.owner!=*referrer_info.key{
+                        msg!("Wrong referrer wSOL account");
+                        return Err(ProgramError::IncorrectProgramId);
+                    }
+                    Some(referrer_wsol_info)
+                }
+                _=>None,
             };
 
-            let (_vault, vault_bump) = Pubkey::find_program_address(&["vault".as_bytes()], &program_id);
+            let lamports = price.checked_mul(amount).ok_or(ProgramError::InvalidArgument)?;
+            let mut commission = 0;
 
-            invoke(
-                &system_instruction::transfer(payer.key, &treasury, price_data.price*amount),
-                &[
-                    payer.clone(),
-                    treasury_info.clone(),
-                    system_program.clone(),
-                ],
+            if let Some((referrer_info, referrer_data_info)) = referrer_infos{
+                if referrer_info.key==payer.key{
+                    msg!("Payer can't refer themselves");
+                    return Err(ProgramError::InvalidArgument);
+                }
+
+                commission = (lamports as u128 * config.referral_bps as u128 / TOTAL_BPS as u128) as u64;
+
+                let mut referrer_data = if referrer_data_info.owner!=program_id{
+                    let (referrer_address, referrer_bump) =
+                        Pubkey::find_program_address(&["referrer".as_bytes(), referrer_info.key.as_ref()], program_id);
+                    if *referrer_data_info.key!=referrer_address{
+                        msg!("Wrong referrer account");
+                        return Err(ProgramError::IncorrectProgramId);
+                    }
+                    create_pda_account(
+                        payer,
+                        referrer_data_info,
+                        system_program,
+                        &Rent::get()?,
+                        ReferrerData::SIZE,
+                        program_id,
+                        &[b"referrer", referrer_info.key.as_ref(), &[referrer_bump]],
+                    )?;
+                    ReferrerData{referrer: *referrer_info.key, purchases: 0, tokens_sold: 0, lamports_earned: 0}
+                } else {
+                    // Referrer data is only ever written at ["referrer", referrer], so a matching
+                    // wallet proves the address without searching for the bump.
+                    let referrer_data = read_account::<ReferrerData>(referrer_data_info)?;
+                    if referrer_data.referrer!=*referrer_info.key{
+                        msg!("Wrong referrer account");
+                        return Err(ProgramError::IncorrectProgramId);
+                    }
+                    referrer_data
+                };
+
+                if commission>0{
+                    match (wsol_infos, referrer_wsol_info){
+                        (Some((payer_wsol_info, _)), Some(referrer_wsol_info))=>
+                            transfer_wsol(token_info, payer_wsol_info, referrer_wsol_info, payer, commission)?,
+                        _=>invoke(
+                            &system_instruction::transfer(payer.key, referrer_info.key, commission),
+                            &[
+                                payer.clone(),
+                                referrer_info.clone(),
+                                system_program.clone(),
+                            ],
+                        )?,
+                    }
+                }
+
+                referrer_data.purchases = referrer_data.purchases.saturating_add(1);
+                referrer_data.tokens_sold = referrer_data.tokens_sold.saturating_add(amount);
+                referrer_data.lamports_earned = referrer_data.lamports_earned.saturating_add(commission);
+                write_account(referrer_data_info, &referrer_data)?;
+            }
+
+            match wsol_infos{
+                Some((payer_wsol_info, treasury_wsol_info))=>
+                    transfer_wsol(token_info, payer_wsol_info, treasury_wsol_info, payer, lamports - commission)?,
+                None=>invoke(
+                    &system_instruction::transfer(payer.key, treasury_info.key, lamports - commission),
+                    &[
+                        payer.clone(),
+                        treasury_info.clone(),
+                        system_program.clone(),
+                    ],
+                )?,
+            }
+
+            let mut treasury_data = read_account::<TreasuryData>(treasury_info)?;
+            treasury_data.total_raised = treasury_data.total_raised.saturating_add(lamports - commission);
+            write_account(treasury_info, &treasury_data)?;
+
+            create_token_account_if_missing(
+                payer,
+                recipient_info,
+                recipient_mint_holder_info,
+                mint_info,
+                system_program,
+                token_info,
+                rent_info,
+                assoc_acccount_info,
             )?;
 
-            if payer_mint_holder_info.owner != token_info.key{
-                invoke(
-                    &spl_associated_token_account::create_associated_token_account(
-                        payer.key,
-                        payer.key,
+            if config.mint_on_demand{
+                invoke_signed(
+                    &spl_token::instruction::mint_to(
+                        token_info.key,
                         mint_info.key,
-                    ),
+                        recipient_mint_holder_info.key,
+                        vault_info.key,
+                        &[],
+                        amount,
+                    )?,
                     &[
-                        payer.clone(), 
-                        payer_mint_holder_info.clone(), 
-                        payer.clone(),
                         mint_info.clone(),
-                        system_program.clone(),
-                        token_info.clone(),
-                        rent_info.clone(),
-                        assoc_acccount_info.clone(),
+                        recipient_mint_holder_info.clone(),
+                        vault_info.clone(),
+                        token_info.clone()
                     ],
-                    
+                    &[&[b"vault", &[bumps.vault]]],
+                )?;
+            } else {
+                invoke_signed(
+                    &spl_token::instruction::transfer(
+                        token_info.key,
+                        vault_mint_info.key,
+                        recipient_mint_holder_info.key,
+                        vault_info.key,
+                        &[],
+                        amount,
+                    )?,
+                    &[
+                        vault_mint_info.clone(),
+                        recipient_mint_holder_info.clone(),
+                        vault_info.clone(), 
+                        token_info.clone()
+                    ],
+                    &[&[b"vault", &[bumps.vault]]],
                 )?;
             }
 
-            invoke_signed(
-                &spl_token::instruction::transfer(
-                    token_info.key,
-                    vault_mint_info.key,
-                    payer_mint_holder_info.key,
-                    vault_info.key,
-                    &[],
-                    amount,
-                )?,
-                &[
-                    vault_mint_info.clone(),
-                    payer_mint_holder_info.clone(),
-                    vault_info.clone(), 
-                    token_info.clone()
-                ],
-                &[&[b"vault", &[vault_bump]]],
-            )?;
+            state.sold = state.sold.saturating_add(amount);
+            if let Some(index) = active_round{
+                state.rounds[index].sold = state.rounds[index].sold.saturating_add(amount);
+            }
+            write_account(state_info, &state)?;
+            emit(SellEvent::Bought{
+                buyer: *payer.key,
+                amount,
+                lamports,
+                price,
+                referrer: referrer_infos.map(|(referrer_info, _)| *referrer_info.key),
+                commission,
+                recipient: *recipient_info.key,
+                payment,
+            })?;
         }
 
     };
@@ -344,3 +3541,161 @@ pub fn process_instruction(
     Ok(())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer_info<'a>(key: &'a Pubkey, lamports: &'a mut u64, is_signer: bool) -> AccountInfo<'a> {
+        AccountInfo::new(key, is_signer, false, lamports, &mut [], &solana_program::system_program::ID, false, 0)
+    }
+
+    #[test]
+    fn approvals_need_the_threshold() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let admins = AdminData{signers: keys.clone(), threshold: 2, roles: vec![]};
+        let (mut a, mut b, mut c) = (0, 0, 0);
+
+        let one = [signer_info(&keys[0], &mut a, true), signer_info(&keys[1], &mut b, false)];
+        assert_eq!(check_approvals(&admins, &one), Err(ProgramError::MissingRequiredSignature));
+
+        let two = [signer_info(&keys[0], &mut a, true), signer_info(&keys[2], &mut c, true)];
+        assert_eq!(check_approvals(&admins, &two), Ok(()));
+    }
+
+    #[test]
+    fn approvals_count_each_admin_once() {
+        let keys: Vec<Pubkey> = (0..2).map(|_| Pubkey::new_unique()).collect();
+        let outsider = Pubkey::new_unique();
+        let admins = AdminData{signers: keys.clone(), threshold: 2, roles: vec![]};
+        let (mut a, mut b, mut c) = (0, 0, 0);
+
+        let repeated = [signer_info(&keys[0], &mut a, true), signer_info(&keys[0], &mut b, true), signer_info(&outsider, &mut c, true)];
+        assert_eq!(check_approvals(&admins, &repeated), Err(ProgramError::MissingRequiredSignature));
+    }
+
+    #[test]
+    fn pending_price_waits_for_its_time() {
+        let mut price = PriceData{price: 100, pending_price: 120, pending_ts: 1_000, ..PriceData::default()};
+        assert!(!price.apply_pending(999));
+        assert_eq!(price.price, 100);
+
+        assert!(price.apply_pending(1_000));
+        assert_eq!(price, PriceData{price: 120, ..PriceData::default()});
+        assert!(!price.apply_pending(2_000));
+        assert_eq!(price.price, 120);
+    }
+
+    #[test]
+    fn version_4_states_keep_what_follows_the_bumps() {
+        let old_bumps = [1u8, 2, 3, 4, 5, 6];
+        let mut body = (10u64, 4u64, 3u64, 2u64, old_bumps, 7u64, Vec::<SaleRound>::new()).try_to_vec().unwrap();
+        body.resize(SaleState::LEN - 1, 0);
+
+        SaleState::upgrade_body(4, &mut body);
+        let state: SaleState = try_from_slice_unchecked(&body).unwrap();
+        assert_eq!(state.bumps, Bumps{vault: 1, treasury: 2, price: 3, config: 4, state: 5, admin: 6, unwrap: 0});
+        assert_eq!((state.deposited, state.burned, state.distributed), (10, 2, 7));
+        assert!(state.rounds.is_empty());
+    }
+
+    fn event_log(version: u8, event: &[u8]) -> String {
+        let mut bytes = vec![version];
+        bytes.extend_from_slice(&42u64.to_le_bytes());
+        bytes.extend_from_slice(event);
+        format!("Program log: {}{}", EVENT_LOG_PREFIX, base64::encode(bytes))
+    }
+
+    #[test]
+    fn current_events_round_trip() {
+        let event = SellEvent::PriceSet{price: 7};
+        let record = decode_event(&event_log(EVENT_VERSION, &event.try_to_vec().unwrap())).unwrap();
+        assert_eq!(record, EventRecord{version: EVENT_VERSION, slot: 42, event});
+
+        assert_eq!(decode_event("Program log: Success"), None);
+        assert_eq!(decode_event(&event_log(EVENT_VERSION + 1, &SellEvent::PriceSet{price: 7}.try_to_vec().unwrap())), None);
+    }
+
+    fn bounded_config() -> SaleConfig {
+        SaleConfig{price_floor: 50, price_ceiling: 1_000, max_price_change_bps: 1_000, ..SaleConfig::default()}
+    }
+
+    #[test]
+    fn prices_stay_within_bounds() {
+        let config = bounded_config();
+        assert_eq!(config.check_bounds(0), Err(SellError::ZeroPrice.into()));
+        assert_eq!(config.check_bounds(49), Err(SellError::PriceBelowFloor.into()));
+        assert_eq!(config.check_bounds(1_001), Err(SellError::PriceAboveCeiling.into()));
+        assert_eq!(config.check_bounds(1_000), Ok(()));
+    }
+
+    #[test]
+    fn price_updates_move_at_most_the_change_limit() {
+        let config = bounded_config();
+        assert_eq!(config.check_price(100, 110), Ok(()));
+        assert_eq!(config.check_price(100, 90), Ok(()));
+        assert_eq!(config.check_price(100, 111), Err(SellError::PriceChangeTooLarge.into()));
+        assert_eq!(config.check_price(100, 89), Err(SellError::PriceChangeTooLarge.into()));
+    }
+
+    #[test]
+    fn price_window_limits_the_cumulative_move() {
+        let config = SaleConfig{price_window: 1_000, max_window_change_bps: 1_500, ..bounded_config()};
+        let mut price = PriceData{price: 100, ..PriceData::default()};
+
+        assert_eq!(config.check_price_window(&mut price, 110, 5_000), Ok(()));
+        assert_eq!((price.window_start, price.window_price), (5_000, 100));
+        price.price = 110;
+
+        // Each step is within the per-update limit, but together they leave the window's 15%.
+        assert_eq!(config.check_price_window(&mut price, 116, 5_999), Err(SellError::PriceChangeTooLarge.into()));
+        assert_eq!(config.check_price_window(&mut price, 115, 5_999), Ok(()));
+        price.price = 115;
+
+        assert_eq!(config.check_price_window(&mut price, 126, 6_000), Ok(()));
+        assert_eq!((price.window_start, price.window_price), (6_000, 115));
+    }
+
+    #[test]
+    fn purchases_need_the_minimum_in_whole_lots() {
+        let config = SaleConfig{min_purchase: 20, lot_size: 10, ..SaleConfig::default()};
+        assert_eq!(config.check_amount(10), Err(SellError::BelowMinimumPurchase.into()));
+        assert_eq!(config.check_amount(25), Err(SellError::NotALotMultiple.into()));
+        assert_eq!(config.check_amount(20), Ok(()));
+        assert_eq!(config.check_amount(30), Ok(()));
+
+        // Without limits anything but zero goes, and a lot size of one is no lot at all.
+        let config = SaleConfig{lot_size: 1, ..SaleConfig::default()};
+        assert_eq!(config.check_amount(0), Err(SellError::BelowMinimumPurchase.into()));
+        assert_eq!(config.check_amount(7), Ok(()));
+    }
+
+    /// Root and per-wallet proofs of a three-wallet tree: ((a, b), c).
+    fn three_wallet_tree() -> ([Pubkey; 3], [u8; 32], [Vec<[u8; 32]>; 3]) {
+        let wallets = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let [a, b, c] = wallets.map(|wallet| allowlist_leaf(&wallet));
+        let ab = allowlist_node(&a, &b);
+        (wallets, allowlist_node(&ab, &c), [vec![b, c], vec![a, c], vec![ab]])
+    }
+
+    #[test]
+    fn allowlist_proofs_admit_their_wallet_only() {
+        let (wallets, root, proofs) = three_wallet_tree();
+        for (wallet, proof) in wallets.iter().zip(&proofs){
+            assert!(verify_allowlist(&root, wallet, proof));
+        }
+        assert!(!verify_allowlist(&root, &wallets[0], &proofs[1]));
+        assert!(!verify_allowlist(&root, &Pubkey::new_unique(), &proofs[0]));
+        assert!(!verify_allowlist(&root, &wallets[0], &proofs[0][..1]));
+        // A single wallet is its own root, with an empty proof.
+        assert!(verify_allowlist(&allowlist_leaf(&wallets[0]), &wallets[0], &[]));
+    }
+
+    #[test]
+    fn allowlist_nodes_cant_pass_for_wallets() {
+        let (wallets, root, _proofs) = three_wallet_tree();
+        // The node over a and b, read as a wallet, with c as its proof, hashes as a leaf and misses.
+        let ab = allowlist_node(&allowlist_leaf(&wallets[0]), &allowlist_leaf(&wallets[1]));
+        assert!(!verify_allowlist(&root, &Pubkey::new_from_array(ab), &[allowlist_leaf(&wallets[2])]));
+    }
+}
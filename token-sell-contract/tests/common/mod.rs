@@ -0,0 +1,714 @@
+//! Test validator setup and account lists shared by the benchmarks and the behaviour tests.
+// Each test crate compiles its own copy and uses only part of it.
+#![allow(dead_code)]
+
+use solana_program::program_option::COption;
+use solana_program::program_pack::Pack;
+use solana_program_test::{BanksClient, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signature, Signer},
+    system_instruction, system_program, sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use token_sell::{
+    unpack_account, AdminData, FillMode, PaymentSource, Payout, Role, SaleRound, SellError, SellInstruction, Versioned, Voucher,
+    ACCOUNT_HEADER_LEN,
+};
+
+pub const MINT: &str = "5CZJ7e4uUWCogW2z7rvcE2yur6yE7Z7kcGYrFqLLXSL9";
+pub const FAR_FUTURE: i64 = i64::MAX / 2;
+pub const SUPPLY: u64 = 1_000_000;
+
+pub struct Bench{
+    pub banks_client: BanksClient,
+    /// Kept for `warp_to`, the only way to move the clock.
+    pub context: ProgramTestContext,
+    pub program_id: Pubkey,
+    pub admin: Keypair,
+    pub mint: Pubkey,
+}
+
+pub fn pda(program_id: &Pubkey, seed: &str) -> Pubkey {
+    Pubkey::find_program_address(&[seed.as_bytes()], program_id).0
+}
+
+pub fn ata(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address(owner, mint)
+}
+
+/// Account data the way the program writes it: header, then the borsh body, zero padded.
+pub fn versioned_data<T: Versioned>(value: &T) -> Vec<u8> {
+    let mut data = vec![0u8; T::SIZE];
+    data[..8].copy_from_slice(&T::DISCRIMINATOR);
+    data[8] = T::VERSION;
+    let body = value.try_to_vec().unwrap();
+    data[ACCOUNT_HEADER_LEN..ACCOUNT_HEADER_LEN + body.len()].copy_from_slice(&body);
+    data
+}
+
+pub fn rent_exempt(data: Vec<u8>, owner: Pubkey) -> Account {
+    Account{
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// The native mint, in case the test validator doesn't ship it. `Generate` needs it for the treasury's wSOL ATA.
+pub fn native_mint() -> (Pubkey, Account) {
+    let mut mint_data = vec![0u8; spl_token::state::Mint::LEN];
+    spl_token::state::Mint{
+        mint_authority: COption::None,
+        supply: 0,
+        decimals: 9,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    }.pack_into_slice(&mut mint_data);
+    (spl_token::native_mint::id(), rent_exempt(mint_data, spl_token::id()))
+}
+
+/// The instruction error a failed transaction ended with.
+pub fn failure<T: std::fmt::Debug>(result: Result<T, TransactionError>) -> InstructionError {
+    match result{
+        Err(TransactionError::InstructionError(_, error))=>error,
+        other=>panic!("expected an instruction error, got {:?}", other),
+    }
+}
+
+/// An Ed25519 program instruction checking `signature` of `message` by `signer`, laid out like the
+/// client's. `instruction_index` is where the precompile reads them from; `u16::MAX` means this
+/// instruction's own data, anything else the same offsets in another instruction.
+pub fn ed25519_instruction(signer: &Pubkey, signature: &Signature, message: &[u8], instruction_index: u16) -> Instruction {
+    let public_key_offset: u16 = 2 + 14;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+
+    let mut data = vec![1u8, 0];
+    for offset in [
+        signature_offset,
+        instruction_index,
+        public_key_offset,
+        instruction_index,
+        message_offset,
+        message.len() as u16,
+        instruction_index,
+    ]{
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(signature.as_ref());
+    data.extend_from_slice(message);
+
+    Instruction{program_id: solana_sdk::ed25519_program::id(), accounts: vec![], data}
+}
+
+/// `voucher` signed by `signer` for the sale `program_id`.
+pub fn signed_voucher(signer: &Keypair, program_id: &Pubkey, voucher: &Voucher) -> Instruction {
+    let message = voucher.message(program_id);
+    ed25519_instruction(&signer.pubkey(), &signer.sign_message(&message), &message, u16::MAX)
+}
+
+pub fn sell_error(error: SellError) -> InstructionError {
+    InstructionError::Custom(error as u32)
+}
+
+pub async fn setup() -> Bench {
+    setup_with(|_| vec![]).await
+}
+
+/// Starts a validator with the hardcoded mint (authority: admin), the admin's funded token account
+/// and an "admin" PDA naming a freshly generated admin, since `BOOTSTRAP_ADMIN` can't sign here.
+/// `extra` adds accounts of its own, given the program id.
+pub async fn setup_with(extra: impl Fn(&Pubkey) -> Vec<(Pubkey, Account)>) -> Bench {
+    let program_id = Pubkey::new_unique();
+    let admin = Keypair::new();
+    let mint = MINT.parse::<Pubkey>().unwrap();
+
+    // `ProgramTest::new` adds the program before `prefer_bpf` could take effect.
+    let mut program_test = ProgramTest::default();
+    program_test.prefer_bpf(true);
+    program_test.add_program("token_sell", program_id, None);
+
+    program_test.add_account(admin.pubkey(), Account{
+        lamports: 100_000_000_000,
+        owner: system_program::id(),
+        ..Account::default()
+    });
+
+    let mut mint_data = vec![0u8; spl_token::state::Mint::LEN];
+    spl_token::state::Mint{
+        mint_authority: COption::Some(admin.pubkey()),
+        supply: SUPPLY,
+        decimals: 0,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    }.pack_into_slice(&mut mint_data);
+    program_test.add_account(mint, rent_exempt(mint_data, spl_token::id()));
+
+    let mut holder_data = vec![0u8; spl_token::state::Account::LEN];
+    spl_token::state::Account{
+        mint,
+        owner: admin.pubkey(),
+        amount: SUPPLY,
+        state: spl_token::state::AccountState::Initialized,
+        ..spl_token::state::Account::default()
+    }.pack_into_slice(&mut holder_data);
+    program_test.add_account(ata(&admin.pubkey(), &mint), rent_exempt(holder_data, spl_token::id()));
+
+    let (native_mint_address, native_mint_account) = native_mint();
+    program_test.add_account(native_mint_address, native_mint_account);
+
+    let admins = AdminData{signers: vec![admin.pubkey()], threshold: 1, roles: Vec::new()};
+    program_test.add_account(pda(&program_id, "admin"), rent_exempt(versioned_data(&admins), program_id));
+
+    for (address, account) in extra(&program_id){
+        program_test.add_account(address, account);
+    }
+
+    let context = program_test.start_with_context().await;
+    Bench{banks_client: context.banks_client.clone(), context, program_id, admin, mint}
+}
+
+impl Bench{
+    /// Sends `instructions` paid by the first of `signers` and signed by all of them, or paid and
+    /// signed by the admin alone when `signers` is empty. Returns the logs, on failure with the error.
+    pub async fn process(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<Vec<String>, (TransactionError, Vec<String>)> {
+        let recent_blockhash = self.banks_client.get_latest_blockhash().await.unwrap();
+        let signers: Vec<&Keypair> = if signers.is_empty() { vec![&self.admin] } else { signers.to_vec() };
+        let payer = signers[0].pubkey();
+
+        // The 1.10 banks client only hands out the logs of a failed preflight simulation, so the
+        // instructions are simulated first behind a transfer that always fails.
+        let mut probe = instructions.to_vec();
+        probe.push(system_instruction::transfer(&payer, &payer, u64::MAX));
+        let probe = Transaction::new_signed_with_payer(&probe, Some(&payer), &signers, recent_blockhash);
+        let logs = match self.banks_client.process_transaction_with_preflight(probe).await{
+            Err(BanksClientError::SimulationError{err: TransactionError::InstructionError(index, _), logs, ..})
+                if index as usize==instructions.len()=>logs,
+            Err(BanksClientError::SimulationError{err, logs, ..})=>return Err((err, logs)),
+            Err(BanksClientError::TransactionError(err))=>return Err((err, Vec::new())),
+            other=>panic!("the probe transaction should fail on its last instruction, got {:?}", other),
+        };
+
+        let tx = Transaction::new_signed_with_payer(instructions, Some(&payer), &signers, recent_blockhash);
+        match self.banks_client.process_transaction_with_preflight(tx).await{
+            Ok(())=>Ok(logs),
+            Err(BanksClientError::SimulationError{err, logs, ..})=>Err((err, logs)),
+            Err(BanksClientError::TransactionError(err))=>Err((err, logs)),
+            Err(err)=>panic!("{:?}", err),
+        }
+    }
+
+    /// Sends `instruction` paid and signed by the admin, returning the units the program consumed.
+    pub async fn try_run(&mut self, instruction: &SellInstruction, accounts: Vec<AccountMeta>) -> Result<u64, TransactionError> {
+        let instruction = Instruction::new_with_borsh(self.program_id, instruction, accounts);
+        let logs = self.process(&[instruction], &[]).await.map_err(|(err, _logs)| err)?;
+        Ok(self.consumed_units(&logs))
+    }
+
+    /// Like `try_run`, but paid and signed by `signer` in place of the admin, who is `accounts[0]`.
+    pub async fn try_run_as(&mut self, signer: &Keypair, instruction: &SellInstruction, mut accounts: Vec<AccountMeta>) -> Result<(), TransactionError> {
+        accounts[0] = AccountMeta::new(signer.pubkey(), true);
+        let instruction = Instruction::new_with_borsh(self.program_id, instruction, accounts);
+        self.process(&[instruction], &[signer]).await.map(|_logs| ()).map_err(|(err, _logs)| err)
+    }
+
+    /// Like `try_run`, but panics with the logs if the instruction fails.
+    pub async fn run(&mut self, instruction: &SellInstruction, accounts: Vec<AccountMeta>) -> u64 {
+        let program_instruction = Instruction::new_with_borsh(self.program_id, instruction, accounts);
+        match self.process(&[program_instruction], &[]).await{
+            Ok(logs)=>self.consumed_units(&logs),
+            Err((err, logs))=>panic!("{:?} failed: {:?}\n{}", instruction, err, logs.join("\n")),
+        }
+    }
+
+    fn consumed_units(&self, logs: &[String]) -> u64 {
+        let prefix = format!("Program {} consumed ", self.program_id);
+        logs.iter()
+            .rev()
+            .filter_map(|log| log.strip_prefix(&prefix))
+            .find_map(|rest| rest.split(' ').next()?.parse::<u64>().ok())
+            .expect("no compute units in the logs")
+    }
+
+    pub async fn account(&mut self, address: &Pubkey) -> Option<Account> {
+        self.banks_client.get_account(*address).await.unwrap()
+    }
+
+    pub async fn lamports(&mut self, address: &Pubkey) -> u64 {
+        self.account(address).await.map_or(0, |account| account.lamports)
+    }
+
+    /// Decodes one of the program's versioned accounts.
+    pub async fn read<T: Versioned>(&mut self, address: &Pubkey) -> T {
+        let account = self.account(address).await.expect("account doesn't exist");
+        unpack_account(&account.data).unwrap()
+    }
+
+    /// Balance of a token account, 0 if it doesn't exist.
+    pub async fn token_balance(&mut self, address: &Pubkey) -> u64 {
+        match self.account(address).await{
+            Some(account)=>spl_token::state::Account::unpack(&account.data).unwrap().amount,
+            None=>0,
+        }
+    }
+
+    /// Sends lamports from the admin, e.g. to pay a second signer's fees.
+    pub async fn fund(&mut self, wallet: &Pubkey, lamports: u64) {
+        let transfer = system_instruction::transfer(&self.admin.pubkey(), wallet, lamports);
+        self.process(&[transfer], &[]).await.unwrap();
+    }
+
+    pub fn admin_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(pda(&self.program_id, "state"), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "admin"), false),
+        ]
+    }
+
+    pub fn generate_metas(&self) -> Vec<AccountMeta> {
+        let vault = pda(&self.program_id, "vault");
+        vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(pda(&self.program_id, "treasury"), false),
+            AccountMeta::new(ata(&vault, &self.mint), false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            AccountMeta::new(pda(&self.program_id, "price"), false),
+            AccountMeta::new(pda(&self.program_id, "state"), false),
+            AccountMeta::new(ata(&pda(&self.program_id, "treasury"), &spl_token::native_mint::id()), false),
+            AccountMeta::new_readonly(spl_token::native_mint::id(), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "admin"), false),
+        ]
+    }
+
+    /// UnwrapProceeds's account list.
+    pub fn unwrap_metas(&self) -> Vec<AccountMeta> {
+        let treasury = pda(&self.program_id, "treasury");
+        let mut accounts = vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new(treasury, false),
+            AccountMeta::new(ata(&treasury, &spl_token::native_mint::id()), false),
+            AccountMeta::new(pda(&self.program_id, "unwrap"), false),
+            AccountMeta::new_readonly(spl_token::native_mint::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ];
+        accounts.extend(self.admin_metas());
+        accounts
+    }
+
+    /// WithdrawProceeds's account list, paying out to `destination`.
+    pub fn withdraw_proceeds_metas(&self, destination: &Pubkey) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new(pda(&self.program_id, "treasury"), false),
+            AccountMeta::new(*destination, false),
+        ];
+        accounts.extend(self.admin_metas());
+        accounts
+    }
+
+    /// DistributeProceeds's account list, with the split's wallets in their configured order.
+    pub fn distribute_proceeds_metas(&self, recipients: &[Pubkey]) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new(pda(&self.program_id, "treasury"), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "config"), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "state"), false),
+        ];
+        accounts.extend(recipients.iter().map(|wallet| AccountMeta::new(*wallet, false)));
+        accounts
+    }
+
+    /// What the treasury holds above its rent-exempt minimum.
+    pub async fn withdrawable(&mut self) -> u64 {
+        let treasury = self.account(&pda(&self.program_id, "treasury")).await.unwrap();
+        treasury.lamports - Rent::default().minimum_balance(treasury.data.len())
+    }
+
+    pub async fn generate(&mut self) -> u64 {
+        let accounts = self.generate_metas();
+        self.run(&SellInstruction::Generate, accounts).await
+    }
+
+    pub fn set_price_metas(&self) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new(pda(&self.program_id, "price"), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "config"), false),
+        ];
+        accounts.extend(self.admin_metas());
+        accounts
+    }
+
+    pub async fn set_price(&mut self, price: u64) -> u64 {
+        let accounts = self.set_price_metas();
+        self.run(&SellInstruction::SetPrice{price}, accounts).await
+    }
+
+    pub fn schedule_price_metas(&self) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new(pda(&self.program_id, "price"), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "config"), false),
+        ];
+        accounts.extend(self.admin_metas());
+        accounts
+    }
+
+    /// Schedules `price` far enough out that it never takes effect during a test.
+    pub async fn schedule_price(&mut self, price: u64) -> u64 {
+        let accounts = self.schedule_price_metas();
+        self.run(&SellInstruction::SchedulePrice{price, effective_ts: FAR_FUTURE}, accounts).await
+    }
+
+    pub fn price_bounds_metas(&self) -> Vec<AccountMeta> {
+        let mut accounts = self.configure_metas();
+        accounts.insert(2, AccountMeta::new_readonly(pda(&self.program_id, "price"), false));
+        accounts
+    }
+
+    /// Config-only admin instructions share one account list.
+    pub fn configure_metas(&self) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new(pda(&self.program_id, "config"), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ];
+        accounts.extend(self.admin_metas());
+        accounts
+    }
+
+    pub async fn configure(&mut self, instruction: &SellInstruction) -> u64 {
+        let accounts = self.configure_metas();
+        self.run(instruction, accounts).await
+    }
+
+    pub fn token_metas(&self, writable_mint: bool) -> Vec<AccountMeta> {
+        let vault = pda(&self.program_id, "vault");
+        vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(vault, false),
+            AccountMeta::new(ata(&vault, &self.mint), false),
+            if writable_mint { AccountMeta::new(self.mint, false) } else { AccountMeta::new_readonly(self.mint, false) },
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+        ]
+    }
+
+    /// Withdraw's account list, paying `destination`'s ATA out of the vault.
+    pub fn withdraw_metas(&self, destination: &Pubkey) -> Vec<AccountMeta> {
+        let mut accounts = self.token_metas(false);
+        accounts.push(AccountMeta::new_readonly(*destination, false));
+        accounts.push(AccountMeta::new(ata(destination, &self.mint), false));
+        accounts.push(AccountMeta::new(pda(&self.program_id, "state"), false));
+        accounts.push(AccountMeta::new_readonly(pda(&self.program_id, "admin"), false));
+        accounts
+    }
+
+    pub fn withdraw_lamports_metas(&self, destination: &Pubkey) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new(pda(&self.program_id, "vault"), false),
+            AccountMeta::new(*destination, false),
+        ];
+        accounts.extend(self.admin_metas());
+        accounts
+    }
+
+    /// Distribute's account list, each payout followed by its wallet and that wallet's ATA.
+    pub fn distribute_metas(&self, payouts: &[Payout]) -> Vec<AccountMeta> {
+        let mut accounts = self.token_metas(false);
+        accounts.push(AccountMeta::new(pda(&self.program_id, "state"), false));
+        accounts.push(AccountMeta::new_readonly(pda(&self.program_id, "admin"), false));
+        for payout in payouts.iter(){
+            accounts.push(AccountMeta::new_readonly(payout.recipient, false));
+            accounts.push(AccountMeta::new(ata(&payout.recipient, &self.mint), false));
+        }
+        accounts
+    }
+
+    /// Deposit's account list, moving tokens between the admin's ATA and the vault.
+    pub async fn move_tokens(&mut self, instruction: &SellInstruction) -> u64 {
+        let mut accounts = self.token_metas(false);
+        accounts.push(AccountMeta::new(ata(&self.admin.pubkey(), &self.mint), false));
+        accounts.push(AccountMeta::new(pda(&self.program_id, "state"), false));
+        accounts.push(AccountMeta::new_readonly(pda(&self.program_id, "admin"), false));
+        self.run(instruction, accounts).await
+    }
+
+    /// CloseSale's account list, sending leftovers to the admin.
+    pub fn close_sale_metas(&self) -> Vec<AccountMeta> {
+        let destination = self.admin.pubkey();
+        let mut accounts = self.token_metas(false);
+        // CloseSale marks the vault itself as closed.
+        accounts[2] = AccountMeta::new(pda(&self.program_id, "vault"), false);
+        accounts.extend(vec![
+            AccountMeta::new(destination, false),
+            AccountMeta::new(ata(&destination, &self.mint), false),
+            AccountMeta::new(pda(&self.program_id, "price"), false),
+            AccountMeta::new(pda(&self.program_id, "state"), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "admin"), false),
+        ]);
+        accounts
+    }
+
+    /// BurnUnsold's account list; anyone may send it once the sale has ended.
+    pub fn burn_unsold_metas(&self) -> Vec<AccountMeta> {
+        let vault = pda(&self.program_id, "vault");
+        vec![
+            AccountMeta::new_readonly(vault, false),
+            AccountMeta::new(ata(&vault, &self.mint), false),
+            AccountMeta::new(self.mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "config"), false),
+            AccountMeta::new(pda(&self.program_id, "state"), false),
+        ]
+    }
+
+    /// TakeMintAuthority's account list without the system program, which goes after the config;
+    /// ReleaseMintAuthority takes it as is.
+    pub fn mint_authority_metas(&self) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new_readonly(pda(&self.program_id, "vault"), false),
+            AccountMeta::new(self.mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(pda(&self.program_id, "config"), false),
+        ];
+        accounts.extend(self.admin_metas());
+        accounts
+    }
+
+    /// Hands the mint authority to the vault, so purchases mint up to `supply_cap`.
+    pub async fn take_mint_authority(&mut self, supply_cap: u64) -> u64 {
+        let mut accounts = self.mint_authority_metas();
+        accounts.insert(5, AccountMeta::new_readonly(system_program::id(), false));
+        self.run(&SellInstruction::TakeMintAuthority{supply_cap}, accounts).await
+    }
+
+    /// Buy's fixed accounts for a purchase the admin pays for and `recipient` receives.
+    pub fn buy_metas(&self, recipient: &Pubkey) -> Vec<AccountMeta> {
+        let payer = self.admin.pubkey();
+        let mut accounts = self.token_metas(true);
+        accounts.extend(vec![
+            AccountMeta::new(pda(&self.program_id, "treasury"), false),
+            AccountMeta::new_readonly(*recipient, false),
+            AccountMeta::new(ata(recipient, &self.mint), false),
+            AccountMeta::new(pda(&self.program_id, "price"), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "config"), false),
+            AccountMeta::new(pda(&self.program_id, "state"), false),
+            AccountMeta::new_readonly(self.blocklist_address(&payer), false),
+            AccountMeta::new_readonly(self.blocklist_address(recipient), false),
+        ]);
+        accounts
+    }
+
+    pub fn blocklist_address(&self, wallet: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&["blocked".as_bytes(), wallet.as_ref()], &self.program_id).0
+    }
+
+    /// The bumps Buy checks its blocklist PDAs at: the admin's as payer, `recipient`'s and the referrer's.
+    pub fn blocklist_bumps(&self, recipient: &Pubkey, referrer: Option<Pubkey>) -> [u8; 3] {
+        let bump = |wallet: &Pubkey| Pubkey::find_program_address(&["blocked".as_bytes(), wallet.as_ref()], &self.program_id).1;
+        [bump(&self.admin.pubkey()), bump(recipient), referrer.map_or(0, |referrer| bump(&referrer))]
+    }
+
+    /// A plain purchase for `recipient`: whole request or nothing, paid in lamports, outside any allowlist.
+    pub fn purchase(&self, amount: u64, recipient: &Pubkey, referrer: Option<Pubkey>) -> SellInstruction {
+        SellInstruction::Buy{
+            amount,
+            proof: Vec::new(),
+            fill_mode: FillMode::AllOrNothing,
+            payment: PaymentSource::Lamports,
+            blocklist_bumps: self.blocklist_bumps(recipient, referrer),
+        }
+    }
+
+    pub async fn block(&mut self, wallet: Pubkey) -> u64 {
+        let mut accounts = vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new(self.blocklist_address(&wallet), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ];
+        accounts.extend(self.admin_metas());
+        self.run(&SellInstruction::Block{wallet}, accounts).await
+    }
+
+    pub async fn unblock(&mut self, wallet: Pubkey) -> u64 {
+        let mut accounts = vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new(self.blocklist_address(&wallet), false),
+        ];
+        accounts.extend(self.admin_metas());
+        self.run(&SellInstruction::Unblock{wallet}, accounts).await
+    }
+
+    /// GrantRole and RevokeRole's account list.
+    pub fn role_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new(pda(&self.program_id, "admin"), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "state"), false),
+        ]
+    }
+
+    /// Creates a mint of the admin's, other than the sale's.
+    pub async fn create_mint(&mut self) -> Pubkey {
+        let admin = Keypair::from_bytes(&self.admin.to_bytes()).unwrap();
+        let mint = Keypair::new();
+        let instructions = [
+            system_instruction::create_account(
+                &admin.pubkey(),
+                &mint.pubkey(),
+                Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), &admin.pubkey(), None, 0).unwrap(),
+        ];
+        self.process(&instructions, &[&admin, &mint]).await.unwrap();
+        mint.pubkey()
+    }
+
+    /// Mints `amount` of a mint from `create_mint` into a new token account of `owner`'s.
+    pub async fn mint_into_new_account(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+        let admin = Keypair::from_bytes(&self.admin.to_bytes()).unwrap();
+        let account = Keypair::new();
+        let instructions = [
+            system_instruction::create_account(
+                &admin.pubkey(),
+                &account.pubkey(),
+                Rent::default().minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(&spl_token::id(), &account.pubkey(), mint, owner).unwrap(),
+            spl_token::instruction::mint_to(&spl_token::id(), mint, &account.pubkey(), &admin.pubkey(), &[], amount).unwrap(),
+        ];
+        self.process(&instructions, &[&admin, &account]).await.unwrap();
+        account.pubkey()
+    }
+
+    /// Grants `role` to a fresh wallet, funded to pay its own fees.
+    pub async fn role_holder(&mut self, role: Role) -> Keypair {
+        let holder = Keypair::new();
+        let accounts = self.role_metas();
+        self.run(&SellInstruction::GrantRole{wallet: holder.pubkey(), role}, accounts).await;
+        self.fund(&holder.pubkey(), 1_000_000_000).await;
+        holder
+    }
+
+    pub async fn buy(&mut self, amount: u64, referrer: Option<Pubkey>) -> u64 {
+        self.buy_with(amount, referrer, FillMode::AllOrNothing, PaymentSource::Lamports).await
+    }
+
+    /// The admin's purchase accounts for `payment`, with the referrer group if there is one.
+    pub fn referred_buy_metas(&self, referrer: Option<Pubkey>, payment: PaymentSource) -> Vec<AccountMeta> {
+        let mut accounts = self.buy_metas(&self.admin.pubkey());
+        let native_mint = spl_token::native_mint::id();
+        if payment==PaymentSource::WrappedSol{
+            accounts.push(AccountMeta::new(ata(&self.admin.pubkey(), &native_mint), false));
+            accounts.push(AccountMeta::new(ata(&pda(&self.program_id, "treasury"), &native_mint), false));
+        }
+        if let Some(referrer) = referrer{
+            let (referrer_address, _referrer_bump) =
+                Pubkey::find_program_address(&["referrer".as_bytes(), referrer.as_ref()], &self.program_id);
+            accounts.push(AccountMeta::new(referrer, false));
+            accounts.push(AccountMeta::new(referrer_address, false));
+            accounts.push(AccountMeta::new_readonly(self.blocklist_address(&referrer), false));
+            if payment==PaymentSource::WrappedSol{
+                accounts.push(AccountMeta::new(ata(&referrer, &native_mint), false));
+            }
+        }
+        accounts
+    }
+
+    pub async fn buy_with(&mut self, amount: u64, referrer: Option<Pubkey>, fill_mode: FillMode, payment: PaymentSource) -> u64 {
+        let accounts = self.referred_buy_metas(referrer, payment);
+        let blocklist_bumps = self.blocklist_bumps(&self.admin.pubkey(), referrer);
+        self.run(&SellInstruction::Buy{amount, proof: Vec::new(), fill_mode, payment, blocklist_bumps}, accounts).await
+    }
+
+    pub fn set_rounds_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.admin.pubkey(), true),
+            AccountMeta::new_readonly(pda(&self.program_id, "config"), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "price"), false),
+            AccountMeta::new(pda(&self.program_id, "state"), false),
+            AccountMeta::new_readonly(pda(&self.program_id, "admin"), false),
+        ]
+    }
+
+    pub async fn set_rounds(&mut self, rounds: Vec<SaleRound>) -> u64 {
+        let accounts = self.set_rounds_metas();
+        self.run(&SellInstruction::SetRounds{rounds}, accounts).await
+    }
+
+    /// The buyer's ["round", [index], wallet] PDA, which wallet-capped rounds take last.
+    pub fn round_purchase_address(&self, index: u8, wallet: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&["round".as_bytes(), &[index], wallet.as_ref()], &self.program_id).0
+    }
+
+    pub async fn now(&mut self) -> i64 {
+        self.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp
+    }
+
+    /// Moves the clock to `unix_timestamp`, for rounds that have to be set a notice period ahead.
+    pub async fn warp_to(&mut self, unix_timestamp: i64) {
+        let clock = Clock{unix_timestamp, ..self.banks_client.get_sysvar::<Clock>().await.unwrap()};
+        self.context.set_sysvar(&clock);
+    }
+
+    /// Creates the admin's wSOL ATA and wraps `lamports` into it; `Generate` creates the treasury's.
+    pub async fn wrap_sol(&mut self, lamports: u64) {
+        let native_mint = spl_token::native_mint::id();
+        let admin = self.admin.pubkey();
+        let admin_wsol = ata(&admin, &native_mint);
+        let instructions = [
+            spl_associated_token_account::instruction::create_associated_token_account(&admin, &admin, &native_mint),
+            system_instruction::transfer(&admin, &admin_wsol, lamports),
+            spl_token::instruction::sync_native(&spl_token::id(), &admin_wsol).unwrap(),
+        ];
+        self.process(&instructions, &[]).await.unwrap();
+    }
+
+    /// Buys `amount` for the admin in one transaction with `vouchers`, the Ed25519 program
+    /// instructions that go before the purchase, passing the nonce PDA of `nonce`.
+    pub async fn try_buy_with_voucher(&mut self, amount: u64, nonce: u64, vouchers: Vec<Instruction>) -> Result<Vec<String>, TransactionError> {
+        let buyer = self.admin.pubkey();
+        let (nonce_address, _nonce_bump) =
+            Pubkey::find_program_address(&["voucher".as_bytes(), buyer.as_ref(), &nonce.to_le_bytes()], &self.program_id);
+        let mut accounts = self.buy_metas(&buyer);
+        accounts.push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
+        accounts.push(AccountMeta::new(nonce_address, false));
+
+        let mut instructions = vouchers;
+        instructions.push(Instruction::new_with_borsh(self.program_id, &self.purchase(amount, &buyer, None), accounts));
+        self.process(&instructions, &[]).await.map_err(|(err, _logs)| err)
+    }
+
+    /// Generate, open at 1000 lamports per token and stock the vault.
+    pub async fn open_sale(&mut self) {
+        self.generate().await;
+        self.set_price(1_000).await;
+        self.move_tokens(&SellInstruction::Deposit{amount: SUPPLY / 2}).await;
+    }
+}
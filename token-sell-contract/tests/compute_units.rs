@@ -0,0 +1,438 @@
+//! Compute-unit benchmarks, one per instruction. They run the BPF build, so build it first:
+//! `cargo build-bpf && cargo test-bpf --test compute_units -- --nocapture`.
+//! Each test prints what its instruction consumed and fails once it grows past the budget.
+
+mod common;
+
+use common::*;
+use solana_program_test::tokio;
+use solana_sdk::{
+    instruction::AccountMeta,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program,
+};
+use token_sell::{
+    FillMode, LimitTarget, PaymentSource, Payout, Role, SaleRound, SellInstruction, SplitShare, MAX_DISTRIBUTE_PAYOUTS,
+    MIN_PRICE_DELAY,
+};
+
+fn check_budget(name: &str, units: u64, budget: u64) {
+    println!("{}: {} compute units (budget {})", name, units, budget);
+    assert!(units<=budget, "{} used {} compute units, over its budget of {}", name, units, budget);
+}
+
+#[tokio::test]
+async fn generate() {
+    let mut bench = setup().await;
+    let units = bench.generate().await;
+    check_budget("Generate", units, 150_000);
+}
+
+#[tokio::test]
+async fn set_price() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let units = bench.set_price(1_000).await;
+    check_budget("SetPrice", units, 20_000);
+}
+
+#[tokio::test]
+async fn schedule_price() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    bench.set_price(1_000).await;
+    let units = bench.schedule_price(1_100).await;
+    check_budget("SchedulePrice", units, 20_000);
+}
+
+#[tokio::test]
+async fn cancel_price_change() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    bench.set_price(1_000).await;
+    bench.schedule_price(1_100).await;
+    let mut accounts = vec![
+        AccountMeta::new(bench.admin.pubkey(), true),
+        AccountMeta::new(pda(&bench.program_id, "price"), false),
+    ];
+    accounts.extend(bench.admin_metas());
+    let units = bench.run(&SellInstruction::CancelPriceChange, accounts).await;
+    check_budget("CancelPriceChange", units, 15_000);
+}
+
+#[tokio::test]
+async fn set_price_bounds() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let accounts = bench.price_bounds_metas();
+    let bounds = SellInstruction::SetPriceBounds{floor: 100, ceiling: 10_000, max_change_bps: 2_000, window_secs: 86_400, max_window_change_bps: 3_000};
+    let units = bench.run(&bounds, accounts).await;
+    check_budget("SetPriceBounds", units, 20_000);
+}
+
+#[tokio::test]
+async fn set_sale_end() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let units = bench.configure(&SellInstruction::SetSaleEnd{end_ts: i64::MAX / 2}).await;
+    check_budget("SetSaleEnd", units, 20_000);
+}
+
+#[tokio::test]
+async fn set_paused() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let units = bench.configure(&SellInstruction::SetPaused{paused: true}).await;
+    check_budget("SetPaused", units, 20_000);
+}
+
+#[tokio::test]
+async fn set_voucher_signer() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let units = bench.configure(&SellInstruction::SetVoucherSigner{signer: Pubkey::new_unique()}).await;
+    check_budget("SetVoucherSigner", units, 20_000);
+}
+
+#[tokio::test]
+async fn set_token_gate() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let instruction = SellInstruction::SetTokenGate{mint: Pubkey::new_unique(), min_balance: 1, cap_bps: 0};
+    let units = bench.configure(&instruction).await;
+    check_budget("SetTokenGate", units, 20_000);
+}
+
+#[tokio::test]
+async fn set_purchase_limits() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let units = bench.configure(&SellInstruction::SetPurchaseLimits{min_purchase: 10, lot_size: 5}).await;
+    check_budget("SetPurchaseLimits", units, 20_000);
+}
+
+#[tokio::test]
+async fn set_limit_target() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let units = bench.configure(&SellInstruction::SetLimitTarget{target: LimitTarget::Recipient}).await;
+    check_budget("SetLimitTarget", units, 20_000);
+}
+
+#[tokio::test]
+async fn set_referral_bps() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let units = bench.configure(&SellInstruction::SetReferralBps{bps: 500}).await;
+    check_budget("SetReferralBps", units, 20_000);
+}
+
+#[tokio::test]
+async fn set_split() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let recipients = vec![SplitShare{wallet: bench.admin.pubkey(), bps: 10_000}];
+    let dust_recipient = bench.admin.pubkey();
+    let units = bench.configure(&SellInstruction::SetSplit{recipients, dust_recipient}).await;
+    check_budget("SetSplit", units, 20_000);
+}
+
+#[tokio::test]
+async fn deposit() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let units = bench.move_tokens(&SellInstruction::Deposit{amount: 1_000}).await;
+    check_budget("Deposit", units, 30_000);
+}
+
+#[tokio::test]
+async fn withdraw() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    // A fresh destination, so this includes creating its ATA.
+    let destination = Pubkey::new_unique();
+    let accounts = bench.withdraw_metas(&destination);
+    let units = bench.run(&SellInstruction::Withdraw{amount: 1_000, destination}, accounts).await;
+    check_budget("Withdraw", units, 50_000);
+}
+
+#[tokio::test]
+async fn withdraw_lamports() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let destination = bench.admin.pubkey();
+    let accounts = bench.withdraw_lamports_metas(&destination);
+    let units = bench.run(&SellInstruction::WithdrawLamports{lamports: 0, destination}, accounts).await;
+    check_budget("WithdrawLamports", units, 15_000);
+}
+
+#[tokio::test]
+async fn distribute() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    // Fresh wallets, so every payout also pays for creating an ATA.
+    let payouts: Vec<Payout> = (0..MAX_DISTRIBUTE_PAYOUTS)
+        .map(|_| Payout{recipient: Pubkey::new_unique(), amount: 100})
+        .collect();
+    let accounts = bench.distribute_metas(&payouts);
+    let units = bench.run(&SellInstruction::Distribute{payouts}, accounts).await;
+    check_budget("Distribute (full batch)", units, 200_000);
+}
+
+#[tokio::test]
+async fn buy() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let units = bench.buy(10, None).await;
+    check_budget("Buy", units, 60_000);
+}
+
+#[tokio::test]
+async fn buy_gift() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let recipient = Pubkey::new_unique();
+    let accounts = bench.buy_metas(&recipient);
+    bench.run(&bench.purchase(10, &recipient, None), accounts).await;
+    // Once the recipient's ATA exists a gift only adds the second blocklist check to a plain Buy.
+    let accounts = bench.buy_metas(&recipient);
+    let units = bench.run(&bench.purchase(11, &recipient, None), accounts).await;
+    check_budget("Buy (gift)", units, 60_000);
+}
+
+#[tokio::test]
+async fn buy_partial_fill() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    // The vault only holds half the supply, so this fills short.
+    let units = bench.buy_with(SUPPLY, None, FillMode::Partial, PaymentSource::Lamports).await;
+    check_budget("Buy (partial fill)", units, 60_000);
+}
+
+#[tokio::test]
+async fn buy_with_referrer() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    bench.configure(&SellInstruction::SetReferralBps{bps: 500}).await;
+    let referrer = Pubkey::new_unique();
+    bench.buy(10, Some(referrer)).await;
+    // The second referred purchase is the common case: the referrer PDA already exists.
+    let units = bench.buy(11, Some(referrer)).await;
+    check_budget("Buy (referred)", units, 70_000);
+}
+
+/// A round opening as soon as the price notice allows, without a wallet cap or allowlist.
+async fn open_round(bench: &mut Bench) -> SaleRound {
+    let start_ts = bench.now().await + MIN_PRICE_DELAY;
+    SaleRound{start_ts, end_ts: i64::MAX, price: 2_000, allocation: SUPPLY, ..SaleRound::default()}
+}
+
+#[tokio::test]
+async fn set_rounds() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let round = open_round(&mut bench).await;
+    let units = bench.set_rounds(vec![round]).await;
+    check_budget("SetRounds", units, 25_000);
+}
+
+#[tokio::test]
+async fn buy_in_round() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let round = open_round(&mut bench).await;
+    bench.set_rounds(vec![round.clone()]).await;
+    bench.warp_to(round.start_ts).await;
+    let units = bench.buy(10, None).await;
+    check_budget("Buy (round)", units, 60_000);
+}
+
+#[tokio::test]
+async fn withdraw_proceeds() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    bench.buy(10, None).await;
+    let destination = bench.admin.pubkey();
+    let mut accounts = vec![
+        AccountMeta::new(bench.admin.pubkey(), true),
+        AccountMeta::new(pda(&bench.program_id, "treasury"), false),
+        AccountMeta::new(destination, false),
+    ];
+    accounts.extend(bench.admin_metas());
+    let units = bench.run(&SellInstruction::WithdrawProceeds{lamports: 5_000, destination}, accounts).await;
+    check_budget("WithdrawProceeds", units, 15_000);
+}
+
+#[tokio::test]
+async fn buy_wrapped_sol() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    bench.wrap_sol(1_000_000).await;
+    let units = bench.buy_with(10, None, FillMode::AllOrNothing, PaymentSource::WrappedSol).await;
+    check_budget("Buy (wrapped SOL)", units, 60_000);
+}
+
+#[tokio::test]
+async fn unwrap_proceeds() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    bench.wrap_sol(1_000_000).await;
+    bench.buy_with(10, None, FillMode::AllOrNothing, PaymentSource::WrappedSol).await;
+    let accounts = bench.unwrap_metas();
+    let units = bench.run(&SellInstruction::UnwrapProceeds, accounts).await;
+    check_budget("UnwrapProceeds", units, 40_000);
+}
+
+#[tokio::test]
+async fn distribute_proceeds() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let recipients = vec![SplitShare{wallet: bench.admin.pubkey(), bps: 10_000}];
+    let dust_recipient = bench.admin.pubkey();
+    bench.configure(&SellInstruction::SetSplit{recipients, dust_recipient}).await;
+    bench.buy(10, None).await;
+    let accounts = vec![
+        AccountMeta::new(pda(&bench.program_id, "treasury"), false),
+        AccountMeta::new_readonly(pda(&bench.program_id, "config"), false),
+        AccountMeta::new_readonly(pda(&bench.program_id, "state"), false),
+        AccountMeta::new(bench.admin.pubkey(), false),
+    ];
+    let units = bench.run(&SellInstruction::DistributeProceeds, accounts).await;
+    check_budget("DistributeProceeds", units, 20_000);
+}
+
+#[tokio::test]
+async fn burn_unsold() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    bench.configure(&SellInstruction::SetSaleEnd{end_ts: 1}).await;
+    let vault = pda(&bench.program_id, "vault");
+    let accounts = vec![
+        AccountMeta::new_readonly(vault, false),
+        AccountMeta::new(ata(&vault, &bench.mint), false),
+        AccountMeta::new(bench.mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(pda(&bench.program_id, "config"), false),
+        AccountMeta::new(pda(&bench.program_id, "state"), false),
+    ];
+    let units = bench.run(&SellInstruction::BurnUnsold, accounts).await;
+    check_budget("BurnUnsold", units, 30_000);
+}
+
+#[tokio::test]
+async fn close_sale() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let destination = bench.admin.pubkey();
+    let accounts = bench.close_sale_metas();
+    let units = bench.run(&SellInstruction::CloseSale{destination}, accounts).await;
+    check_budget("CloseSale", units, 40_000);
+}
+
+fn mint_authority_metas(bench: &Bench, with_system_program: bool) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new(bench.admin.pubkey(), true),
+        AccountMeta::new_readonly(pda(&bench.program_id, "vault"), false),
+        AccountMeta::new(bench.mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(pda(&bench.program_id, "config"), false),
+    ];
+    if with_system_program{
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    }
+    accounts.extend(bench.admin_metas());
+    accounts
+}
+
+#[tokio::test]
+async fn take_mint_authority() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let accounts = mint_authority_metas(&bench, true);
+    let units = bench.run(&SellInstruction::TakeMintAuthority{supply_cap: SUPPLY * 2}, accounts).await;
+    check_budget("TakeMintAuthority", units, 30_000);
+}
+
+#[tokio::test]
+async fn release_mint_authority() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let accounts = mint_authority_metas(&bench, true);
+    bench.run(&SellInstruction::TakeMintAuthority{supply_cap: SUPPLY * 2}, accounts).await;
+    let accounts = mint_authority_metas(&bench, false);
+    let new_authority = bench.admin.pubkey();
+    let units = bench.run(&SellInstruction::ReleaseMintAuthority{new_authority}, accounts).await;
+    check_budget("ReleaseMintAuthority", units, 25_000);
+}
+
+#[tokio::test]
+async fn set_admins() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let co_signer = Keypair::new();
+    let accounts = vec![
+        AccountMeta::new(bench.admin.pubkey(), true),
+        AccountMeta::new(pda(&bench.program_id, "admin"), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(pda(&bench.program_id, "state"), false),
+    ];
+    let signers = vec![bench.admin.pubkey(), co_signer.pubkey()];
+    let units = bench.run(&SellInstruction::SetAdmins{signers, threshold: 2}, accounts).await;
+    check_budget("SetAdmins", units, 20_000);
+}
+
+#[tokio::test]
+async fn grant_role() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let accounts = bench.role_metas();
+    let wallet = Pubkey::new_unique();
+    let units = bench.run(&SellInstruction::GrantRole{wallet, role: Role::Price}, accounts).await;
+    check_budget("GrantRole", units, 20_000);
+}
+
+#[tokio::test]
+async fn revoke_role() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let wallet = Pubkey::new_unique();
+    let accounts = bench.role_metas();
+    bench.run(&SellInstruction::GrantRole{wallet, role: Role::Price}, accounts).await;
+    let accounts = bench.role_metas();
+    let units = bench.run(&SellInstruction::RevokeRole{wallet, role: Role::Price}, accounts).await;
+    check_budget("RevokeRole", units, 20_000);
+}
+
+#[tokio::test]
+async fn block() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let units = bench.block(Pubkey::new_unique()).await;
+    check_budget("Block", units, 25_000);
+}
+
+#[tokio::test]
+async fn unblock() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let wallet = Pubkey::new_unique();
+    bench.block(wallet).await;
+    let units = bench.unblock(wallet).await;
+    check_budget("Unblock", units, 20_000);
+}
+
+#[tokio::test]
+async fn migrate() {
+    // A price account from before versioning: nothing but the 8-byte price.
+    let mut bench = setup_with(|program_id| vec![
+        (pda(program_id, "price"), rent_exempt(1_000u64.to_le_bytes().to_vec(), *program_id)),
+    ]).await;
+    let accounts = vec![
+        AccountMeta::new(bench.admin.pubkey(), true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(pda(&bench.program_id, "price"), false),
+    ];
+    let units = bench.run(&SellInstruction::Migrate, accounts).await;
+    check_budget("Migrate", units, 30_000);
+}
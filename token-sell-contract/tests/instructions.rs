@@ -0,0 +1,740 @@
+//! Behaviour tests: what instructions accept, refuse and leave behind. Like the benchmarks
+//! they run the BPF build: `cargo build-bpf && cargo test-bpf --test instructions`.
+
+mod common;
+
+use common::*;
+use solana_program::{program_option::COption, program_pack::Pack};
+use solana_program_test::tokio;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signature, Signer},
+    system_program,
+    transaction::TransactionError,
+};
+use token_sell::{
+    allowlist_leaf, is_closed_vault, Bumps, FillMode, LimitTarget, PaymentSource, Payout, PriceData, ReferrerData, Role,
+    RoundPurchase, SaleConfig, SaleRound, SaleState, SellError, SellInstruction, SplitShare, TreasuryData, Voucher,
+    MAX_DISTRIBUTE_PAYOUTS, MIN_PRICE_DELAY,
+};
+
+#[tokio::test]
+async fn generate_adopts_a_baseline_vault() {
+    // What the original Generate left behind: an assigned, empty vault PDA and nothing else.
+    let mut bench = setup_with(|program_id| vec![
+        (pda(program_id, "vault"), Account{lamports: 2_282_880, owner: *program_id, ..Account::default()}),
+    ]).await;
+    bench.generate().await;
+
+    let state: SaleState = bench.read(&pda(&bench.program_id, "state")).await;
+    assert_eq!(state.bumps, Bumps::find(&bench.program_id));
+
+    bench.move_tokens(&SellInstruction::Deposit{amount: 10}).await;
+    let vault_holder = ata(&pda(&bench.program_id, "vault"), &bench.mint);
+    assert_eq!(bench.token_balance(&vault_holder).await, 10);
+}
+
+#[tokio::test]
+async fn closed_sale_stays_closed() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let destination = bench.admin.pubkey();
+    let accounts = bench.close_sale_metas();
+    bench.run(&SellInstruction::CloseSale{destination}, accounts).await;
+
+    let vault = bench.account(&pda(&bench.program_id, "vault")).await.unwrap();
+    assert!(is_closed_vault(&vault.data));
+    assert!(bench.account(&pda(&bench.program_id, "state")).await.is_none());
+
+    let accounts = bench.generate_metas();
+    let result = bench.try_run(&SellInstruction::Generate, accounts).await;
+    assert_eq!(failure(result), InstructionError::InvalidAccountData);
+}
+
+#[tokio::test]
+async fn price_bounds_have_to_cover_the_current_price() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    bench.set_price(50).await;
+
+    let accounts = bench.price_bounds_metas();
+    let bounds = SellInstruction::SetPriceBounds{floor: 100, ceiling: 10_000, max_change_bps: 0, window_secs: 0, max_window_change_bps: 0};
+    let result = bench.try_run(&bounds, accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::PriceBelowFloor));
+}
+
+#[tokio::test]
+async fn scheduled_prices_respect_the_window_limit() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    bench.set_price(1_000).await;
+    let accounts = bench.price_bounds_metas();
+    bench.run(&SellInstruction::SetPriceBounds{floor: 1, ceiling: 10_000, max_change_bps: 3_000, window_secs: 86_400, max_window_change_bps: 2_000}, accounts).await;
+
+    // Within the per-update limit, beyond what the window allows.
+    let accounts = bench.schedule_price_metas();
+    let result = bench.try_run(&SellInstruction::SchedulePrice{price: 1_250, effective_ts: FAR_FUTURE}, accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::PriceChangeTooLarge));
+
+    bench.schedule_price(1_200).await;
+    let price: PriceData = bench.read(&pda(&bench.program_id, "price")).await;
+    assert_eq!((price.pending_price, price.window_price), (1_200, 1_000));
+}
+
+#[tokio::test]
+async fn migrate_adopts_a_baseline_price() {
+    // The original SetPrice wrote nothing but the 8-byte price.
+    let mut bench = setup_with(|program_id| vec![
+        (pda(program_id, "price"), rent_exempt(1_000u64.to_le_bytes().to_vec(), *program_id)),
+    ]).await;
+    let price_address = pda(&bench.program_id, "price");
+    let accounts = vec![
+        AccountMeta::new(bench.admin.pubkey(), true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(price_address, false),
+    ];
+    bench.run(&SellInstruction::Migrate, accounts).await;
+
+    let price: PriceData = bench.read(&price_address).await;
+    assert_eq!(price, PriceData{price: 1_000, ..PriceData::default()});
+}
+
+#[tokio::test]
+async fn only_withdraw_holders_choose_who_gets_the_proceeds() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let configurer = bench.role_holder(Role::Config).await;
+    let withdrawer = bench.role_holder(Role::Withdraw).await;
+
+    let split = |wallet: Pubkey| SellInstruction::SetSplit{recipients: vec![SplitShare{wallet, bps: 10_000}], dust_recipient: wallet};
+    let accounts = bench.configure_metas();
+    let result = bench.try_run_as(&configurer, &split(configurer.pubkey()), accounts).await;
+    assert_eq!(failure(result), InstructionError::MissingRequiredSignature);
+    let accounts = bench.configure_metas();
+    let result = bench.try_run_as(&configurer, &SellInstruction::SetReferralBps{bps: 5_000}, accounts).await;
+    assert_eq!(failure(result), InstructionError::MissingRequiredSignature);
+
+    let accounts = bench.configure_metas();
+    bench.try_run_as(&withdrawer, &split(withdrawer.pubkey()), accounts).await.unwrap();
+    let accounts = bench.configure_metas();
+    bench.try_run_as(&withdrawer, &SellInstruction::SetReferralBps{bps: 500}, accounts).await.unwrap();
+    let config: SaleConfig = bench.read(&pda(&bench.program_id, "config")).await;
+    assert_eq!((config.split[0].wallet, config.referral_bps), (withdrawer.pubkey(), 500));
+}
+
+/// An open sale whose purchases need vouchers signed by the returned key.
+async fn voucher_sale() -> (Bench, Keypair) {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let signer = Keypair::new();
+    bench.configure(&SellInstruction::SetVoucherSigner{signer: signer.pubkey()}).await;
+    (bench, signer)
+}
+
+fn voucher_for(bench: &Bench, max_amount: u64, nonce: u64) -> Voucher {
+    Voucher{buyer: bench.admin.pubkey(), max_amount, expiry: FAR_FUTURE, nonce}
+}
+
+#[tokio::test]
+async fn vouchers_admit_their_buyer_once() {
+    let (mut bench, signer) = voucher_sale().await;
+    let voucher = voucher_for(&bench, 10, 1);
+    let signed = signed_voucher(&signer, &bench.program_id, &voucher);
+
+    bench.try_buy_with_voucher(10, 1, vec![signed.clone()]).await.unwrap();
+    assert_eq!(bench.token_balance(&ata(&bench.admin.pubkey(), &bench.mint)).await, SUPPLY / 2 + 10);
+
+    // Replayed with a different amount, so it isn't the same transaction.
+    let result = bench.try_buy_with_voucher(9, 1, vec![signed]).await;
+    assert_eq!(failure(result), sell_error(SellError::VoucherUsed));
+}
+
+#[tokio::test]
+async fn vouchers_need_the_configured_signer() {
+    let (mut bench, _signer) = voucher_sale().await;
+    let voucher = voucher_for(&bench, 10, 1);
+    let signed = signed_voucher(&Keypair::new(), &bench.program_id, &voucher);
+
+    let result = bench.try_buy_with_voucher(10, 1, vec![signed]).await;
+    assert_eq!(failure(result), sell_error(SellError::InvalidVoucher));
+}
+
+#[tokio::test]
+async fn vouchers_bind_buyer_amount_and_expiry() {
+    let (mut bench, signer) = voucher_sale().await;
+
+    let someone_else = Voucher{buyer: Pubkey::new_unique(), ..voucher_for(&bench, 10, 1)};
+    let result = bench.try_buy_with_voucher(10, 1, vec![signed_voucher(&signer, &bench.program_id, &someone_else)]).await;
+    assert_eq!(failure(result), sell_error(SellError::InvalidVoucher));
+
+    let smaller = voucher_for(&bench, 5, 2);
+    let result = bench.try_buy_with_voucher(10, 2, vec![signed_voucher(&signer, &bench.program_id, &smaller)]).await;
+    assert_eq!(failure(result), sell_error(SellError::VoucherAmountExceeded));
+
+    let expired = Voucher{expiry: 1, ..voucher_for(&bench, 10, 3)};
+    let result = bench.try_buy_with_voucher(10, 3, vec![signed_voucher(&signer, &bench.program_id, &expired)]).await;
+    assert_eq!(failure(result), sell_error(SellError::VoucherExpired));
+}
+
+#[tokio::test]
+async fn vouchers_must_sit_in_their_own_instruction() {
+    let (mut bench, signer) = voucher_sale().await;
+    let message = voucher_for(&bench, 1_000, 1).message(&bench.program_id);
+
+    // Instruction 1 carries a voucher by the real signer, but points the precompile at
+    // instruction 0, where a stranger's signature of other bytes sits at the same offsets.
+    let stranger = Keypair::new();
+    let decoy = vec![7u8; message.len()];
+    let checked = ed25519_instruction(&stranger.pubkey(), &stranger.sign_message(&decoy), &decoy, u16::MAX);
+    let unchecked = ed25519_instruction(&signer.pubkey(), &Signature::default(), &message, 0);
+
+    let result = bench.try_buy_with_voucher(10, 1, vec![checked, unchecked]).await;
+    assert_eq!(failure(result), sell_error(SellError::InvalidVoucher));
+}
+
+#[tokio::test]
+async fn blocked_payers_can_buy_again_once_unblocked() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let buyer = bench.admin.pubkey();
+    bench.block(buyer).await;
+
+    let accounts = bench.buy_metas(&buyer);
+    let result = bench.try_run(&bench.purchase(10, &buyer, None), accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::WalletBlocked));
+
+    bench.unblock(buyer).await;
+    bench.buy(10, None).await;
+    assert_eq!(bench.token_balance(&ata(&buyer, &bench.mint)).await, SUPPLY / 2 + 10);
+}
+
+#[tokio::test]
+async fn blocked_wallets_can_neither_receive_nor_refer() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let blocked = Pubkey::new_unique();
+    bench.block(blocked).await;
+
+    let accounts = bench.buy_metas(&blocked);
+    let result = bench.try_run(&bench.purchase(10, &blocked, None), accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::WalletBlocked));
+
+    let accounts = bench.referred_buy_metas(Some(blocked), PaymentSource::Lamports);
+    let result = bench.try_run(&bench.purchase(10, &bench.admin.pubkey(), Some(blocked)), accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::WalletBlocked));
+
+    // Anyone else still refers as before.
+    bench.buy(10, Some(Pubkey::new_unique())).await;
+}
+
+/// Buys `amount` for the admin of a gated sale, showing `gate_account` as the admin's holding.
+async fn try_gated_buy(bench: &mut Bench, gate_account: Pubkey, amount: u64) -> Result<u64, TransactionError> {
+    let mut accounts = bench.buy_metas(&bench.admin.pubkey());
+    accounts.push(AccountMeta::new_readonly(gate_account, false));
+    bench.try_run(&bench.purchase(amount, &bench.admin.pubkey(), None), accounts).await
+}
+
+#[tokio::test]
+async fn gated_purchases_need_enough_of_the_gate_mint() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let gate_mint = bench.create_mint().await;
+    bench.configure(&SellInstruction::SetTokenGate{mint: gate_mint, min_balance: 100, cap_bps: 5_000}).await;
+    let buyer = bench.admin.pubkey();
+
+    let short = bench.mint_into_new_account(&gate_mint, &buyer, 99).await;
+    assert_eq!(failure(try_gated_buy(&mut bench, short, 10).await), sell_error(SellError::GateBalanceTooLow));
+
+    // 200 gate tokens at 5,000 bps allow purchases of up to 100.
+    let holding = bench.mint_into_new_account(&gate_mint, &buyer, 200).await;
+    assert_eq!(failure(try_gated_buy(&mut bench, holding, 101).await), sell_error(SellError::GateCapExceeded));
+    try_gated_buy(&mut bench, holding, 100).await.unwrap();
+    assert_eq!(bench.token_balance(&ata(&buyer, &bench.mint)).await, SUPPLY / 2 + 100);
+}
+
+#[tokio::test]
+async fn gate_accounts_have_to_be_the_buyers_holding_of_the_gate_mint() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let gate_mint = bench.create_mint().await;
+    bench.configure(&SellInstruction::SetTokenGate{mint: gate_mint, min_balance: 1, cap_bps: 0}).await;
+    let buyer = bench.admin.pubkey();
+
+    let someone_elses = bench.mint_into_new_account(&gate_mint, &Pubkey::new_unique(), 1_000).await;
+    assert_eq!(failure(try_gated_buy(&mut bench, someone_elses, 10).await), sell_error(SellError::InvalidGateAccount));
+
+    let other_mint = bench.create_mint().await;
+    let wrong_mint = bench.mint_into_new_account(&other_mint, &buyer, 1_000).await;
+    assert_eq!(failure(try_gated_buy(&mut bench, wrong_mint, 10).await), sell_error(SellError::InvalidGateAccount));
+
+    // Not a token account at all.
+    assert_eq!(failure(try_gated_buy(&mut bench, buyer, 10).await), sell_error(SellError::InvalidGateAccount));
+}
+
+/// A round of 100 tokens opening at `start_ts`, at most 10 per wallet.
+fn capped_round(start_ts: i64, price: u64) -> SaleRound {
+    SaleRound{start_ts, end_ts: start_ts + 1_000, price, allocation: 100, wallet_cap: 10, ..SaleRound::default()}
+}
+
+async fn try_round_buy(bench: &mut Bench, index: u8, amount: u64) -> Result<u64, TransactionError> {
+    let mut accounts = bench.referred_buy_metas(None, PaymentSource::Lamports);
+    accounts.push(AccountMeta::new(bench.round_purchase_address(index, &bench.admin.pubkey()), false));
+    let blocklist_bumps = bench.blocklist_bumps(&bench.admin.pubkey(), None);
+    let buy = SellInstruction::Buy{amount, proof: Vec::new(), fill_mode: FillMode::AllOrNothing, payment: PaymentSource::Lamports, blocklist_bumps};
+    bench.try_run(&buy, accounts).await
+}
+
+#[tokio::test]
+async fn round_prices_are_price_changes() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let accounts = bench.price_bounds_metas();
+    bench.run(&SellInstruction::SetPriceBounds{floor: 1, ceiling: 10_000, max_change_bps: 5_000, window_secs: 0, max_window_change_bps: 0}, accounts).await;
+    let configurer = bench.role_holder(Role::Config).await;
+    let pricer = bench.role_holder(Role::Price).await;
+    let start = bench.now().await + MIN_PRICE_DELAY;
+
+    let accounts = bench.set_rounds_metas();
+    let result = bench.try_run_as(&configurer, &SellInstruction::SetRounds{rounds: vec![capped_round(start, 1_500)]}, accounts).await;
+    assert_eq!(failure(result), InstructionError::MissingRequiredSignature);
+
+    // Sooner than SchedulePrice's notice, or further than one update may move the price.
+    let accounts = bench.set_rounds_metas();
+    let result = bench.try_run_as(&pricer, &SellInstruction::SetRounds{rounds: vec![capped_round(start - 60, 1_500)]}, accounts).await;
+    assert_eq!(failure(result), InstructionError::InvalidArgument);
+    let accounts = bench.set_rounds_metas();
+    let result = bench.try_run_as(&pricer, &SellInstruction::SetRounds{rounds: vec![capped_round(start, 1_600)]}, accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::PriceChangeTooLarge));
+    let rounds = vec![capped_round(start, 1_500), capped_round(start + 1_000, 2_300)];
+    let accounts = bench.set_rounds_metas();
+    let result = bench.try_run_as(&pricer, &SellInstruction::SetRounds{rounds}, accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::PriceChangeTooLarge));
+
+    let rounds = vec![capped_round(start, 1_500), capped_round(start + 1_000, 2_200)];
+    let accounts = bench.set_rounds_metas();
+    bench.try_run_as(&pricer, &SellInstruction::SetRounds{rounds}, accounts).await.unwrap();
+}
+
+#[tokio::test]
+async fn wallet_caps_start_over_in_a_new_round() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let start = bench.now().await + MIN_PRICE_DELAY;
+    bench.set_rounds(vec![capped_round(start, 2_000)]).await;
+    bench.warp_to(start).await;
+    try_round_buy(&mut bench, 0, 10).await.unwrap();
+    let result = try_round_buy(&mut bench, 0, 1).await;
+    assert_eq!(failure(result), sell_error(SellError::RoundWalletCapExceeded));
+
+    // A later round in the same place doesn't inherit what the wallet bought in the first.
+    let next_start = start + MIN_PRICE_DELAY;
+    bench.set_rounds(vec![capped_round(next_start, 2_000)]).await;
+    bench.warp_to(next_start).await;
+    try_round_buy(&mut bench, 0, 9).await.unwrap();
+    let purchase: RoundPurchase = bench.read(&bench.round_purchase_address(0, &bench.admin.pubkey())).await;
+    assert_eq!((purchase.bought, purchase.start_ts), (9, next_start));
+}
+
+#[tokio::test]
+async fn purchases_need_the_minimum_in_whole_lots() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    bench.set_price(1_000).await;
+    bench.move_tokens(&SellInstruction::Deposit{amount: 95}).await;
+    bench.configure(&SellInstruction::SetPurchaseLimits{min_purchase: 20, lot_size: 10}).await;
+
+    let blocklist_bumps = bench.blocklist_bumps(&bench.admin.pubkey(), None);
+    let buy = |amount, fill_mode| SellInstruction::Buy{amount, proof: Vec::new(), fill_mode, payment: PaymentSource::Lamports, blocklist_bumps};
+    let accounts = bench.referred_buy_metas(None, PaymentSource::Lamports);
+    let result = bench.try_run(&buy(10, FillMode::AllOrNothing), accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::BelowMinimumPurchase));
+    let accounts = bench.referred_buy_metas(None, PaymentSource::Lamports);
+    let result = bench.try_run(&buy(25, FillMode::AllOrNothing), accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::NotALotMultiple));
+
+    bench.buy(30, None).await;
+    // 65 tokens are left, so a partial fill of 100 stops at the last whole lot.
+    let buyer_holder = ata(&bench.admin.pubkey(), &bench.mint);
+    let before = bench.token_balance(&buyer_holder).await;
+    bench.buy_with(100, None, FillMode::Partial, PaymentSource::Lamports).await;
+    assert_eq!(bench.token_balance(&buyer_holder).await - before, 60);
+    assert_eq!(bench.token_balance(&ata(&pda(&bench.program_id, "vault"), &bench.mint)).await, 5);
+}
+
+#[tokio::test]
+async fn partial_fills_deliver_and_charge_what_the_vault_holds() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    bench.set_price(1_000).await;
+    bench.move_tokens(&SellInstruction::Deposit{amount: 40}).await;
+    let buyer_holder = ata(&bench.admin.pubkey(), &bench.mint);
+    let treasury = pda(&bench.program_id, "treasury");
+    let tokens_before = bench.token_balance(&buyer_holder).await;
+    let lamports_before = bench.lamports(&treasury).await;
+
+    let blocklist_bumps = bench.blocklist_bumps(&bench.admin.pubkey(), None);
+    let buy = |amount, fill_mode| SellInstruction::Buy{amount, proof: Vec::new(), fill_mode, payment: PaymentSource::Lamports, blocklist_bumps};
+    let accounts = bench.referred_buy_metas(None, PaymentSource::Lamports);
+    let result = bench.try_run(&buy(50, FillMode::AllOrNothing), accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::InsufficientInventory));
+
+    bench.buy_with(50, None, FillMode::Partial, PaymentSource::Lamports).await;
+    assert_eq!(bench.token_balance(&buyer_holder).await - tokens_before, 40);
+    assert_eq!(bench.lamports(&treasury).await - lamports_before, 40 * 1_000);
+
+    // Nothing left to fill from.
+    let accounts = bench.referred_buy_metas(None, PaymentSource::Lamports);
+    let result = bench.try_run(&buy(10, FillMode::Partial), accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::InsufficientInventory));
+}
+
+#[tokio::test]
+async fn unwrapping_moves_only_the_proceeds() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    bench.wrap_sol(1_000_000).await;
+    bench.buy_with(10, None, FillMode::AllOrNothing, PaymentSource::WrappedSol).await;
+    let treasury = pda(&bench.program_id, "treasury");
+    let treasury_wsol = ata(&treasury, &spl_token::native_mint::id());
+    let ata_rent = bench.lamports(&treasury_wsol).await - 10 * 1_000;
+    let before = bench.lamports(&treasury).await;
+
+    let accounts = bench.unwrap_metas();
+    bench.run(&SellInstruction::UnwrapProceeds, accounts).await;
+    assert_eq!(bench.lamports(&treasury).await - before, 10 * 1_000);
+    // The ATA stays open for the next wSOL buyer, and the temporary account is gone.
+    assert_eq!((bench.token_balance(&treasury_wsol).await, bench.lamports(&treasury_wsol).await), (0, ata_rent));
+    assert!(bench.account(&pda(&bench.program_id, "unwrap")).await.is_none());
+}
+
+#[tokio::test]
+async fn withdrawn_proceeds_reach_the_destination_and_leave_the_rent() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    bench.buy(1_000, None).await;
+    let treasury = pda(&bench.program_id, "treasury");
+    let destination = Pubkey::new_unique();
+    let available = bench.withdrawable().await;
+    assert!(available>=1_000 * 1_000);
+
+    let accounts = bench.withdraw_proceeds_metas(&destination);
+    bench.run(&SellInstruction::WithdrawProceeds{lamports: available, destination}, accounts).await;
+    assert_eq!(bench.lamports(&destination).await, available);
+    let treasury_account = bench.account(&treasury).await.unwrap();
+    assert_eq!(treasury_account.lamports, Rent::default().minimum_balance(treasury_account.data.len()));
+}
+
+#[tokio::test]
+async fn withdrawals_cannot_touch_the_treasury_rent() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    bench.buy(1_000, None).await;
+    let destination = Pubkey::new_unique();
+    let available = bench.withdrawable().await;
+
+    let accounts = bench.withdraw_proceeds_metas(&destination);
+    let result = bench.try_run(&SellInstruction::WithdrawProceeds{lamports: available + 1, destination}, accounts).await;
+    assert_eq!(failure(result), InstructionError::InsufficientFunds);
+    assert_eq!(bench.withdrawable().await, available);
+}
+
+#[tokio::test]
+async fn uneven_splits_leave_the_dust_to_the_dust_recipient() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let wallets: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    for wallet in wallets.iter(){
+        // Funded, so that small cuts don't leave them below the rent-exempt minimum.
+        bench.fund(wallet, 1_000_000_000).await;
+    }
+    let recipients = vec![
+        SplitShare{wallet: wallets[0], bps: 3_333},
+        SplitShare{wallet: wallets[1], bps: 3_333},
+        SplitShare{wallet: wallets[2], bps: 3_334},
+    ];
+    bench.configure(&SellInstruction::SetSplit{recipients, dust_recipient: wallets[1]}).await;
+    bench.buy(1_001, None).await;
+    assert_eq!(bench.withdrawable().await, 1_001_000);
+
+    let accounts = bench.distribute_proceeds_metas(&wallets);
+    bench.run(&SellInstruction::DistributeProceeds, accounts).await;
+    // 1,001,000 lamports at 3,333/3,333/3,334 bps round down to 333,633/333,633/333,733, one lamport short.
+    for (wallet, lamports) in wallets.iter().zip([333_633, 333_633 + 1, 333_733]){
+        assert_eq!(bench.lamports(wallet).await, 1_000_000_000 + lamports);
+    }
+    assert_eq!(bench.withdrawable().await, 0);
+}
+
+#[tokio::test]
+async fn splits_name_each_wallet_once_with_a_share() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+    let twice = vec![SplitShare{wallet: first, bps: 4_000}, SplitShare{wallet: first, bps: 6_000}];
+    let accounts = bench.configure_metas();
+    let result = bench.try_run(&SellInstruction::SetSplit{recipients: twice, dust_recipient: first}, accounts).await;
+    assert_eq!(failure(result), InstructionError::InvalidArgument);
+
+    let empty_share = vec![SplitShare{wallet: first, bps: 10_000}, SplitShare{wallet: second, bps: 0}];
+    let accounts = bench.configure_metas();
+    let result = bench.try_run(&SellInstruction::SetSplit{recipients: empty_share, dust_recipient: first}, accounts).await;
+    assert_eq!(failure(result), InstructionError::InvalidArgument);
+}
+
+/// An open sale paying referrers 500 bps, and a funded referrer to pay them.
+async fn referral_sale() -> (Bench, Pubkey) {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    bench.configure(&SellInstruction::SetReferralBps{bps: 500}).await;
+    let referrer = Pubkey::new_unique();
+    bench.fund(&referrer, 1_000_000_000).await;
+    (bench, referrer)
+}
+
+#[tokio::test]
+async fn referrers_collect_and_count_their_commissions() {
+    let (mut bench, referrer) = referral_sale().await;
+    bench.buy(100, Some(referrer)).await;
+    bench.buy(200, Some(referrer)).await;
+
+    // 5% of 100,000 and of 200,000 lamports.
+    assert_eq!(bench.lamports(&referrer).await, 1_000_000_000 + 15_000);
+    let referrer_address = Pubkey::find_program_address(&["referrer".as_bytes(), referrer.as_ref()], &bench.program_id).0;
+    let referrer_data: ReferrerData = bench.read(&referrer_address).await;
+    assert_eq!(referrer_data, ReferrerData{referrer, purchases: 2, tokens_sold: 300, lamports_earned: 15_000});
+}
+
+#[tokio::test]
+async fn commissions_come_out_of_the_treasurys_share() {
+    let (mut bench, referrer) = referral_sale().await;
+    let treasury = pda(&bench.program_id, "treasury");
+    let before = bench.lamports(&treasury).await;
+
+    bench.buy(100, Some(referrer)).await;
+    assert_eq!(bench.lamports(&treasury).await - before, 100_000 - 5_000);
+    let treasury_data: TreasuryData = bench.read(&treasury).await;
+    assert_eq!(treasury_data.total_raised, 100_000 - 5_000);
+}
+
+#[tokio::test]
+async fn unsold_tokens_burn_only_after_the_sale_end() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    bench.buy(100, None).await;
+    let end = bench.now().await + 1_000;
+    bench.configure(&SellInstruction::SetSaleEnd{end_ts: end}).await;
+
+    let accounts = bench.burn_unsold_metas();
+    let result = bench.try_run(&SellInstruction::BurnUnsold, accounts).await;
+    assert_eq!(failure(result), InstructionError::InvalidArgument);
+
+    bench.warp_to(end).await;
+    let accounts = bench.burn_unsold_metas();
+    bench.run(&SellInstruction::BurnUnsold, accounts).await;
+    assert_eq!(bench.token_balance(&ata(&pda(&bench.program_id, "vault"), &bench.mint)).await, 0);
+    let state: SaleState = bench.read(&pda(&bench.program_id, "state")).await;
+    assert_eq!(state.burned, SUPPLY / 2 - 100);
+}
+
+#[tokio::test]
+async fn minted_sales_stop_at_the_supply_cap_and_leave_the_vault_alone() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    bench.set_price(1_000).await;
+    bench.move_tokens(&SellInstruction::Deposit{amount: 50}).await;
+    bench.take_mint_authority(SUPPLY + 100).await;
+    let buyer_holder = ata(&bench.admin.pubkey(), &bench.mint);
+    let vault_holder = ata(&pda(&bench.program_id, "vault"), &bench.mint);
+    let before = bench.token_balance(&buyer_holder).await;
+
+    bench.buy(60, None).await;
+    assert_eq!(bench.token_balance(&buyer_holder).await - before, 60);
+    assert_eq!(bench.token_balance(&vault_holder).await, 50);
+    let mint = bench.mint;
+    let mint = spl_token::state::Mint::unpack(&bench.account(&mint).await.unwrap().data).unwrap();
+    assert_eq!(mint.supply, SUPPLY + 60);
+
+    // 40 more reach the cap, though the vault would have had 50.
+    let accounts = bench.referred_buy_metas(None, PaymentSource::Lamports);
+    let result = bench.try_run(&bench.purchase(41, &bench.admin.pubkey(), None), accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::InsufficientInventory));
+}
+
+#[tokio::test]
+async fn released_mint_authority_goes_where_it_is_sent() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    bench.take_mint_authority(SUPPLY).await;
+    let new_authority = Pubkey::new_unique();
+
+    let accounts = bench.mint_authority_metas();
+    bench.run(&SellInstruction::ReleaseMintAuthority{new_authority}, accounts).await;
+    let mint = bench.mint;
+    let mint = spl_token::state::Mint::unpack(&bench.account(&mint).await.unwrap().data).unwrap();
+    assert_eq!(mint.mint_authority, COption::Some(new_authority));
+    let config: SaleConfig = bench.read(&pda(&bench.program_id, "config")).await;
+    assert!(!config.mint_on_demand);
+}
+
+#[tokio::test]
+async fn gifts_land_in_the_recipients_account() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let recipient = Pubkey::new_unique();
+    let payer_holder = ata(&bench.admin.pubkey(), &bench.mint);
+    let before = bench.token_balance(&payer_holder).await;
+
+    let accounts = bench.buy_metas(&recipient);
+    bench.run(&bench.purchase(25, &recipient, None), accounts).await;
+    assert_eq!(bench.token_balance(&ata(&recipient, &bench.mint)).await, 25);
+    assert_eq!(bench.token_balance(&payer_holder).await, before);
+}
+
+#[tokio::test]
+async fn round_limits_follow_the_limit_target() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let recipient = Pubkey::new_unique();
+    let start = bench.now().await + MIN_PRICE_DELAY;
+    // Only the recipient is on the allowlist, a single leaf with an empty proof.
+    let round = SaleRound{allowlist_root: allowlist_leaf(&recipient), ..capped_round(start, 1_000)};
+    bench.set_rounds(vec![round]).await;
+    bench.warp_to(start).await;
+    let gift = |bench: &Bench, amount: u64, wallet: &Pubkey| {
+        let mut accounts = bench.buy_metas(&recipient);
+        accounts.push(AccountMeta::new(bench.round_purchase_address(0, wallet), false));
+        (bench.purchase(amount, &recipient, None), accounts)
+    };
+
+    // By default the payer has to be on the allowlist.
+    let (buy, accounts) = gift(&bench, 5, &bench.admin.pubkey());
+    let result = bench.try_run(&buy, accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::NotAllowlisted));
+
+    bench.configure(&SellInstruction::SetLimitTarget{target: LimitTarget::Recipient}).await;
+    let (buy, accounts) = gift(&bench, 6, &recipient);
+    bench.run(&buy, accounts).await;
+    let purchase: RoundPurchase = bench.read(&bench.round_purchase_address(0, &recipient)).await;
+    assert_eq!((purchase.wallet, purchase.bought), (recipient, 6));
+    // The recipient's wallet cap of 10 is what the next gift runs into.
+    let (buy, accounts) = gift(&bench, 5, &recipient);
+    let result = bench.try_run(&buy, accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::RoundWalletCapExceeded));
+    assert_eq!(bench.token_balance(&ata(&recipient, &bench.mint)).await, 6);
+}
+
+#[tokio::test]
+async fn distributions_pay_each_recipient_and_open_missing_accounts() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let admin_holder = ata(&bench.admin.pubkey(), &bench.mint);
+    let admin_before = bench.token_balance(&admin_holder).await;
+    let fresh = Pubkey::new_unique();
+    assert!(bench.account(&ata(&fresh, &bench.mint)).await.is_none());
+
+    let payouts = vec![Payout{recipient: bench.admin.pubkey(), amount: 30}, Payout{recipient: fresh, amount: 70}];
+    let accounts = bench.distribute_metas(&payouts);
+    bench.run(&SellInstruction::Distribute{payouts}, accounts).await;
+    assert_eq!(bench.token_balance(&admin_holder).await - admin_before, 30);
+    assert_eq!(bench.token_balance(&ata(&fresh, &bench.mint)).await, 70);
+    let state: SaleState = bench.read(&pda(&bench.program_id, "state")).await;
+    assert_eq!(state.distributed, 100);
+}
+
+#[tokio::test]
+async fn distributions_take_a_bounded_batch() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let payouts: Vec<Payout> = (0..=MAX_DISTRIBUTE_PAYOUTS)
+        .map(|_| Payout{recipient: Pubkey::new_unique(), amount: 1})
+        .collect();
+    let accounts = bench.distribute_metas(&payouts);
+    let result = bench.try_run(&SellInstruction::Distribute{payouts}, accounts).await;
+    assert_eq!(failure(result), InstructionError::InvalidInstructionData);
+}
+
+#[tokio::test]
+async fn vault_lamport_withdrawals_leave_the_rent() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let vault = pda(&bench.program_id, "vault");
+    bench.fund(&vault, 5_000_000).await;
+    let data_len = bench.account(&vault).await.unwrap().data.len();
+    let available = bench.lamports(&vault).await - Rent::default().minimum_balance(data_len).max(1);
+    let destination = Pubkey::new_unique();
+    bench.fund(&destination, 1_000_000_000).await;
+
+    let accounts = bench.withdraw_lamports_metas(&destination);
+    let result = bench.try_run(&SellInstruction::WithdrawLamports{lamports: available + 1, destination}, accounts).await;
+    assert_eq!(failure(result), InstructionError::InsufficientFunds);
+    let accounts = bench.withdraw_lamports_metas(&destination);
+    bench.run(&SellInstruction::WithdrawLamports{lamports: available, destination}, accounts).await;
+    assert_eq!(bench.lamports(&destination).await, 1_000_000_000 + available);
+
+    // Closing tops the drained vault back up for the marker it keeps.
+    let accounts = bench.close_sale_metas();
+    let destination = bench.admin.pubkey();
+    bench.run(&SellInstruction::CloseSale{destination}, accounts).await;
+    let vault = bench.account(&vault).await.unwrap();
+    assert!(is_closed_vault(&vault.data));
+    assert!(Rent::default().is_exempt(vault.lamports, vault.data.len()));
+}
+
+#[tokio::test]
+async fn withdrawals_open_a_third_partys_account() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let destination = Pubkey::new_unique();
+    assert!(bench.account(&ata(&destination, &bench.mint)).await.is_none());
+
+    let accounts = bench.withdraw_metas(&destination);
+    bench.run(&SellInstruction::Withdraw{amount: 1_000, destination}, accounts).await;
+    assert_eq!(bench.token_balance(&ata(&destination, &bench.mint)).await, 1_000);
+    let state: SaleState = bench.read(&pda(&bench.program_id, "state")).await;
+    assert_eq!(state.withdrawn, 1_000);
+}
+
+#[tokio::test]
+async fn opening_prices_are_set_once() {
+    let mut bench = setup().await;
+    bench.generate().await;
+    bench.set_price(1_000).await;
+
+    let accounts = bench.set_price_metas();
+    let result = bench.try_run(&SellInstruction::SetPrice{price: 1_200}, accounts).await;
+    assert_eq!(failure(result), sell_error(SellError::PriceAlreadySet));
+    let price: PriceData = bench.read(&pda(&bench.program_id, "price")).await;
+    assert_eq!(price.price, 1_000);
+}
+
+#[tokio::test]
+async fn blocklist_checks_take_only_the_canonical_bump() {
+    let mut bench = setup().await;
+    bench.open_sale().await;
+    let buyer = bench.admin.pubkey();
+    bench.block(buyer).await;
+
+    // A lower bump that is also off the curve names an empty account, which must not pass for the entry.
+    let [canonical, recipient_bump, _] = bench.blocklist_bumps(&buyer, None);
+    let entry_address = |bump: u8| Pubkey::create_program_address(&["blocked".as_bytes(), buyer.as_ref(), &[bump]], &bench.program_id);
+    let (bump, address) = (0..canonical).rev()
+        .find_map(|bump| entry_address(bump).ok().map(|address| (bump, address)))
+        .unwrap();
+    let mut accounts = bench.buy_metas(&buyer);
+    let entries = accounts.len() - 2;
+    accounts[entries] = AccountMeta::new_readonly(address, false);
+    accounts[entries + 1] = AccountMeta::new_readonly(address, false);
+    let buy = SellInstruction::Buy{
+        amount: 10,
+        proof: Vec::new(),
+        fill_mode: FillMode::AllOrNothing,
+        payment: PaymentSource::Lamports,
+        blocklist_bumps: [bump, bump, recipient_bump],
+    };
+    let result = bench.try_run(&buy, accounts).await;
+    assert_eq!(failure(result), InstructionError::IncorrectProgramId);
+}